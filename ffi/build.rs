@@ -0,0 +1,56 @@
+// Copyright (c) 2016 Ivan Nejgebauer <inejge@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Generate `env_proxy.h` from the declarations of the C interface into `OUT_DIR`. The copy in
+//! `include/` is checked against it by the tests of the package.
+
+use std::env;
+use std::path::PathBuf;
+
+const HEADER: &str = "/*
+ * Copyright (c) 2016 Ivan Nejgebauer <inejge@gmail.com>
+ *
+ * Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+ * http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+ * http://opensource.org/licenses/MIT>, at your option. This file may not be
+ * copied, modified, or distributed except according to those terms.
+ */
+
+/*
+ * C interface of env_proxy, built with
+ *
+ *     cargo build --release -p env_proxy-ffi
+ *
+ * All strings are NUL-terminated UTF-8. Strings returned by the library must be
+ * released with env_proxy_result_free() or env_proxy_string_free().
+ *
+ * This file is generated by the build of env_proxy-ffi; don't edit it. To update
+ * it, copy env_proxy.h from the OUT_DIR of the build, as reported by the failing
+ * test of env_proxy-ffi.
+ */";
+
+fn main() {
+    let source = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap()).join("..").join("src").join("ffi.rs");
+    println!("cargo:rerun-if-changed={}", source.display());
+    println!("cargo:rerun-if-changed=build.rs");
+    let mut config = cbindgen::Config {
+        header: Some(HEADER.to_string()),
+        include_guard: Some("ENV_PROXY_H".to_string()),
+        cpp_compat: true,
+        language: cbindgen::Language::C,
+        style: cbindgen::Style::Both,
+        documentation: false,
+        ..Default::default()
+    };
+    config.export.rename.insert("EnvProxyResult".to_string(), "env_proxy_result".to_string());
+    cbindgen::Builder::new()
+        .with_config(config)
+        .with_src(&source)
+        .generate()
+        .expect("can't generate the C header")
+        .write_to_file(PathBuf::from(env::var("OUT_DIR").unwrap()).join("env_proxy.h"));
+}
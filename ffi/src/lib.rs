@@ -0,0 +1,22 @@
+// Copyright (c) 2016 Ivan Nejgebauer <inejge@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! The C interface of `env_proxy`, built as `libenv_proxy_c.so` (or the platform's equivalent)
+//! and `libenv_proxy_c.a`. The functions are those of the `ffi` module of `env_proxy`, declared
+//! in `include/env_proxy.h`, which is kept in sync with the header generated by the build.
+
+pub use env_proxy::ffi::*;
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn header_up_to_date() {
+        let generated = concat!(env!("OUT_DIR"), "/env_proxy.h");
+        assert!(include_str!(concat!(env!("OUT_DIR"), "/env_proxy.h")) == include_str!("../../include/env_proxy.h"),
+            "include/env_proxy.h is out of date; copy it from {}", generated);
+    }
+}
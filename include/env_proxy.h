@@ -0,0 +1,63 @@
+/*
+ * Copyright (c) 2016 Ivan Nejgebauer <inejge@gmail.com>
+ *
+ * Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+ * http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+ * http://opensource.org/licenses/MIT>, at your option. This file may not be
+ * copied, modified, or distributed except according to those terms.
+ */
+
+/*
+ * C interface of env_proxy, built with
+ *
+ *     cargo build --release -p env_proxy-ffi
+ *
+ * All strings are NUL-terminated UTF-8. Strings returned by the library must be
+ * released with env_proxy_result_free() or env_proxy_string_free().
+ *
+ * This file is generated by the build of env_proxy-ffi; don't edit it. To update
+ * it, copy env_proxy.h from the OUT_DIR of the build, as reported by the failing
+ * test of env_proxy-ffi.
+ */
+
+#ifndef ENV_PROXY_H
+#define ENV_PROXY_H
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+#define ENV_PROXY_OK 0
+
+#define ENV_PROXY_INVALID_TARGET 1
+
+#define ENV_PROXY_INVALID_PROXY 2
+
+typedef struct env_proxy_result {
+  char *scheme;
+  char *host;
+  uint16_t port;
+  char *user;
+  char *password;
+  char *source;
+  int bypass;
+} env_proxy_result;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+int env_proxy_for_url(const char *url, struct env_proxy_result *result);
+
+void env_proxy_result_free(struct env_proxy_result *result);
+
+char *env_proxy_url(const char *url);
+
+void env_proxy_string_free(char *s);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* ENV_PROXY_H */
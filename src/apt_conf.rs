@@ -0,0 +1,213 @@
+// Copyright (c) 2016 Ivan Nejgebauer <inejge@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use url::Url;
+
+use crate::{ProviderResult, ProxyProvider};
+
+/// The value which makes apt connect directly.
+const DIRECT: &str = "DIRECT";
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Word(String),
+    Str(String),
+    Open,
+    Close,
+    End,
+}
+
+fn tokenize(contents: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut chars = contents.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => (),
+            '/' if chars.peek() == Some(&'/') => { chars.by_ref().find(|&c| c == '\n'); },
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut prev = ' ';
+                chars.by_ref().find(|&c| std::mem::replace(&mut prev, c) == '*' && c == '/');
+            },
+            // Comments, and directives such as #include and #clear, which aren't followed.
+            '#' => { chars.by_ref().find(|&c| c == '\n'); },
+            '"' => tokens.push(Token::Str(chars.by_ref().take_while(|&c| c != '"').collect())),
+            '{' => tokens.push(Token::Open),
+            '}' => tokens.push(Token::Close),
+            ';' => tokens.push(Token::End),
+            c => {
+                let mut word = c.to_string();
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || "{};\"".contains(c) {
+                        break;
+                    }
+                    word.push(c);
+                    chars.next();
+                }
+                tokens.push(Token::Word(word));
+            },
+        }
+    }
+    tokens
+}
+
+/// Parse an apt configuration file into the options, keyed by their lowercased full names.
+fn parse_apt_conf(contents: &str, options: &mut BTreeMap<String, String>) {
+    let mut scopes: Vec<String> = Vec::new();
+    let mut key: Option<String> = None;
+    let mut value: Option<String> = None;
+    let full_name = |scopes: &[String], key: &str| {
+        scopes.iter().map(String::as_str).chain(Some(key)).collect::<Vec<_>>().join("::").to_ascii_lowercase()
+    };
+    for token in tokenize(contents) {
+        match token {
+            Token::Word(word) if key.is_none() => key = Some(word),
+            Token::Word(word) | Token::Str(word) => value = Some(word),
+            Token::Open => {
+                scopes.push(key.take().unwrap_or_default());
+                value = None;
+            },
+            Token::Close => {
+                scopes.pop();
+                key = None;
+                value = None;
+            },
+            Token::End => {
+                if let (Some(key), Some(value)) = (key.take(), value.take()) {
+                    options.insert(full_name(&scopes, &key), value);
+                }
+            },
+        }
+    }
+}
+
+/// Return the apt configuration files, in the order apt reads them.
+fn config_paths() -> io::Result<Vec<PathBuf>> {
+    let mut paths = vec![PathBuf::from("/etc/apt/apt.conf")];
+    let parts = Path::new("/etc/apt/apt.conf.d");
+    match fs::read_dir(parts) {
+        Ok(entries) => {
+            let mut names = entries.filter_map(Result::ok)
+                .map(|entry| entry.file_name().to_string_lossy().into_owned())
+                .filter(|name| !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || "_-.".contains(c)))
+                .filter(|name| !name.contains('.') || name.ends_with(".conf"))
+                .collect::<Vec<_>>();
+            names.sort();
+            paths.extend(names.into_iter().map(|name| parts.join(name)));
+        },
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => (),
+        Err(e) => return Err(e),
+    }
+    paths.extend(env::var_os("APT_CONFIG").filter(|path| !path.is_empty()).map(PathBuf::from));
+    Ok(paths)
+}
+
+/// The proxy settings of apt (`Acquire::<scheme>::Proxy` and `Acquire::<scheme>::Proxy::<host>`).
+///
+/// For a target with the `http`, `https` or `ftp` scheme, the host-specific option applies if
+/// present, then the general one; for `https`, the `http` options are consulted if the `https`
+/// ones are absent, as apt does. The value `DIRECT` means no proxy. The provider reports its
+/// proxies under the name `apt`.
+///
+/// The configuration syntax is that of apt: `::`-separated names, nested scopes in braces,
+/// quoted values, and `//`, `/* */` and `#` comments. `#include` and `#clear` are not followed.
+#[derive(Clone, Debug, Default)]
+pub struct AptProxyConfig {
+    options: BTreeMap<String, String>,
+}
+
+impl AptProxyConfig {
+    /// Read the apt configuration: `/etc/apt/apt.conf`, the files in `/etc/apt/apt.conf.d` in
+    /// lexical order, and the file named by __APT_CONFIG__. Missing files are skipped.
+    pub fn load() -> io::Result<AptProxyConfig> {
+        let mut contents = Vec::new();
+        for path in config_paths()? {
+            match fs::read_to_string(path) {
+                Ok(file) => contents.push(file),
+                Err(ref e) if e.kind() == io::ErrorKind::NotFound => (),
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(AptProxyConfig::parse(contents.iter().map(String::as_str)))
+    }
+
+    /// Parse the contents of apt configuration files, in the order apt would read them.
+    pub fn parse<'a, I: IntoIterator<Item = &'a str>>(files: I) -> AptProxyConfig {
+        let mut options = BTreeMap::new();
+        for contents in files {
+            parse_apt_conf(contents, &mut options);
+        }
+        options.retain(|name, _| name.starts_with("acquire::"));
+        AptProxyConfig { options }
+    }
+
+    /// Return the proxy value apt would use for the URL, if any.
+    ///
+    /// The value is returned as written in the configuration, including `DIRECT`.
+    pub fn proxy_for(&self, url: &Url) -> Option<&str> {
+        let host = url.host_str().unwrap_or("").to_ascii_lowercase();
+        let schemes: &[&str] = match url.scheme() {
+            "https" => &["https", "http"],
+            "http" => &["http"],
+            "ftp" => &["ftp"],
+            _ => &[],
+        };
+        schemes.iter()
+            .find_map(|scheme| {
+                self.options.get(&format!("acquire::{}::proxy::{}", scheme, host))
+                    .or_else(|| self.options.get(&format!("acquire::{}::proxy", scheme)))
+            })
+            .map(String::as_str)
+            .filter(|value| !value.is_empty())
+    }
+}
+
+impl ProxyProvider for AptProxyConfig {
+    fn proxies_for(&self, url: &Url) -> ProviderResult {
+        match self.proxy_for(url) {
+            None => ProviderResult::Pass,
+            Some(DIRECT) => ProviderResult::Direct,
+            Some(value) => ProviderResult::Proxies(vec![value.to_string()]),
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        "apt"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use url::Url;
+
+    use super::AptProxyConfig;
+    use crate::{ProviderResult, ProxyProvider};
+
+    #[test]
+    fn apt_conf() {
+        let main = "// proxy settings\nAcquire::http::Proxy \"http://proxy.example.com:3142/\";\n\
+            /* mirrors */ Acquire::http::Proxy::Mirror.example.com \"DIRECT\";\n";
+        let part = "# local overrides\nAcquire {\n  https { Proxy \"http://secure.example.com:3128/\"; };\n\
+            ftp::proxy \"ftp-proxy.example.com:2121\";\n};\nAPT::Get::Assume-Yes \"true\";\n";
+        let config = AptProxyConfig::parse(vec![main, part]);
+        let proxy = |url: &str| config.proxies_for(&Url::parse(url).unwrap());
+        let proxies = |value: &str| ProviderResult::Proxies(vec![value.to_string()]);
+        assert_eq!(proxy("http://deb.example.org/debian"), proxies("http://proxy.example.com:3142/"));
+        assert_eq!(proxy("http://mirror.example.com/debian"), ProviderResult::Direct);
+        assert_eq!(proxy("https://mirror.example.com/debian"), proxies("http://secure.example.com:3128/"));
+        assert_eq!(proxy("ftp://ftp.example.org/"), proxies("ftp-proxy.example.com:2121"));
+        assert_eq!(proxy("ws://ws.example.org/"), ProviderResult::Pass);
+        let config = AptProxyConfig::parse(vec![main]);
+        assert_eq!(config.proxy_for(&Url::parse("https://mirror.example.com/").unwrap()), Some("DIRECT"));
+    }
+}
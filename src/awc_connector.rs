@@ -0,0 +1,216 @@
+// Copyright (c) 2016 Ivan Nejgebauer <inejge@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use actix_rt::net::TcpStream;
+use actix_service::Service;
+use actix_tls::connect::{ConnectError, ConnectInfo, Connection, Connector, Resolver as DnsResolver};
+use awc::http::Uri;
+use tokio::io::{AsyncRead, AsyncWrite};
+use url::Url;
+
+use crate::socks::handshake_tokio;
+use crate::tunnel::tunnel_tokio;
+use crate::{default_resolver, ProxyKind, Resolver};
+
+/// A TCP connector service which opens a `CONNECT` tunnel when the target has a proxy.
+#[derive(Clone)]
+struct TunnelConnector<S> {
+    inner: S,
+    resolver: Resolver,
+}
+
+fn invalid_input<E: std::fmt::Display>(e: E) -> ConnectError {
+    ConnectError::Io(io::Error::new(io::ErrorKind::InvalidInput, e.to_string()))
+}
+
+impl<S, Io> Service<ConnectInfo<Uri>> for TunnelConnector<S>
+    where S: Service<ConnectInfo<Uri>, Response = Connection<Uri, Io>, Error = ConnectError> + Clone + 'static,
+          Io: AsyncRead + AsyncWrite + Unpin + 'static
+{
+    type Response = Connection<Uri, Io>;
+    type Error = ConnectError;
+    type Future = Pin<Box<dyn Future<Output = Result<Connection<Uri, Io>, ConnectError>>>>;
+
+    fn poll_ready(&self, cx: &mut Context) -> Poll<Result<(), ConnectError>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&self, req: ConnectInfo<Uri>) -> Self::Future {
+        let inner = self.inner.clone();
+        // A request with an explicit address is meant to bypass name resolution, and the proxy.
+        if req.addrs().len() > 0 {
+            return Box::pin(async move { inner.call(req).await });
+        }
+        let uri = req.request().clone();
+        let target = Url::parse(&uri.to_string()).map_err(invalid_input);
+        let proxy = target.as_ref().ok().map(|target| self.resolver.try_for_url(target).and_then(|proxy| proxy.try_to_url()));
+        Box::pin(async move {
+            let target = target?;
+            let proxy = match proxy {
+                Some(Ok(Some(proxy))) => proxy,
+                Some(Err(e)) => return Err(invalid_input(e)),
+                _ => return inner.call(req).await,
+            };
+            let socks = match ProxyKind::from_scheme(proxy.scheme()) {
+                Some(ProxyKind::Http) => false,
+                Some(kind) if kind != ProxyKind::Https => true,
+                _ => {
+                    let message = format!("unsupported proxy scheme '{}'", proxy.scheme());
+                    return Err(ConnectError::Io(io::Error::new(io::ErrorKind::Unsupported, message)));
+                },
+            };
+            let host = proxy.host_str().unwrap_or("");
+            let port = proxy.port_or_known_default().unwrap_or(0);
+            let proxy_uri = format!("http://{}:{}", host, port).parse::<Uri>().map_err(invalid_input)?;
+            let (mut stream, _) = inner.call(ConnectInfo::new(proxy_uri)).await?.into_parts();
+            if socks {
+                let port = target.port_or_known_default().unwrap_or(0);
+                handshake_tokio(&mut stream, &proxy, target.host_str().unwrap_or(""), port).await.map_err(ConnectError::Io)?;
+                return Ok(Connection::new(uri, stream));
+            }
+            let authority = format!("{}:{}", target.host_str().unwrap_or(""), target.port_or_known_default().unwrap_or(0));
+            tunnel_tokio(&mut stream, &proxy, &authority).await.map_err(ConnectError::Io)?;
+            Ok(Connection::new(uri, stream))
+        })
+    }
+}
+
+impl Resolver {
+    /// Return an __awc__ connector which routes connections through the proxies determined
+    /// by the resolver (requires the `awc` feature.)
+    ///
+    /// The connector consults the resolver for each new connection. A target without a proxy is
+    /// dialed directly. Otherwise, the connector dials the proxy and opens a `CONNECT` tunnel to
+    /// the target, over which the client establishes TLS for an `https` target. Since __awc__
+    /// always sends requests in origin form, plain `http` targets are tunneled as well; note
+    /// that some proxies only allow tunnels to port 443. With a SOCKS proxy, the proxy is asked
+    /// to connect to the target. HTTPS proxies are not supported; the connection fails with an
+    /// error of the `Unsupported` kind. A request with an explicitly set peer address is connected
+    /// directly.
+    ///
+    /// The connector can be further configured before it's passed to `awc::ClientBuilder::connector()`.
+    pub fn awc_connector(&self) -> awc::Connector<impl Service<ConnectInfo<Uri>, Response = Connection<Uri, TcpStream>, Error = ConnectError> + Clone> {
+        let inner = Connector::new(DnsResolver::default()).service();
+        awc::Connector::new().connector(TunnelConnector { inner, resolver: self.clone() })
+    }
+}
+
+/// Return an __awc__ connector which routes connections through the proxies from the environment
+/// (requires the `awc` feature.)
+///
+/// The proxies are determined with the resolver of [`for_url()`](fn.for_url.html), as described
+/// for [`Resolver::awc_connector()`](struct.Resolver.html#method.awc_connector).
+///
+/// ```no_run
+/// # extern crate awc;
+/// # extern crate env_proxy;
+/// # async fn get() -> Result<(), Box<dyn std::error::Error>> {
+/// let client = awc::Client::builder().connector(env_proxy::awc_connector()).finish();
+/// let response = client.get("https://www.example.com/").send().await?;
+/// println!("{}", response.status());
+/// # Ok(())
+/// # }
+/// # fn main() {}
+/// ```
+pub fn awc_connector() -> awc::Connector<impl Service<ConnectInfo<Uri>, Response = Connection<Uri, TcpStream>, Error = ConnectError> + Clone> {
+    default_resolver().awc_connector()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::future::{ready, Ready};
+    use std::io::{self, Read as _, Write as _};
+    use std::pin::Pin;
+    use std::sync::{Arc, Mutex};
+    use std::task::{Context, Poll, Waker};
+
+    use actix_service::Service;
+    use actix_tls::connect::{ConnectError, ConnectInfo, Connection};
+    use awc::http::Uri;
+    use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+    use super::TunnelConnector;
+    use crate::tunnel::tests::MockStream;
+    use crate::Resolver;
+
+    impl AsyncRead for MockStream {
+        fn poll_read(self: Pin<&mut Self>, _cx: &mut Context, buf: &mut ReadBuf) -> Poll<io::Result<()>> {
+            let mut byte = [0u8];
+            let n = self.get_mut().read(&mut byte)?;
+            buf.put_slice(&byte[..n]);
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    impl AsyncWrite for MockStream {
+        fn poll_write(self: Pin<&mut Self>, _cx: &mut Context, buf: &[u8]) -> Poll<io::Result<usize>> {
+            Poll::Ready(self.get_mut().write(buf))
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    /// A connector which records the dialed URIs and returns mock streams.
+    #[derive(Clone, Default)]
+    struct MockConnector {
+        dialed: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl Service<ConnectInfo<Uri>> for MockConnector {
+        type Response = Connection<Uri, MockStream>;
+        type Error = ConnectError;
+        type Future = Ready<Result<Connection<Uri, MockStream>, ConnectError>>;
+
+        actix_service::always_ready!();
+
+        fn call(&self, req: ConnectInfo<Uri>) -> Self::Future {
+            self.dialed.lock().unwrap().push(req.request().to_string());
+            let stream = MockStream::new(b"HTTP/1.1 200 Connection established\r\n\r\n");
+            ready(Ok(Connection::new(req.request().clone(), stream)))
+        }
+    }
+
+    fn connect(connector: &TunnelConnector<MockConnector>, uri: &str) -> Result<Connection<Uri, MockStream>, ConnectError> {
+        let mut fut = connector.call(ConnectInfo::new(uri.parse().unwrap()));
+        match fut.as_mut().poll(&mut Context::from_waker(Waker::noop())) {
+            Poll::Ready(res) => res,
+            Poll::Pending => panic!("mock connector is never pending"),
+        }
+    }
+
+    #[test]
+    fn awc_tunnel() {
+        let resolver = Resolver::new().with_vars(vec![("all_proxy", "user:secret@proxy.example.com:3128"), ("no_proxy", "example.org")]);
+        let inner = MockConnector::default();
+        let connector = TunnelConnector { inner: inner.clone(), resolver };
+        let conn = connect(&connector, "https://www.example.com/").unwrap();
+        assert_eq!(conn.request().to_string(), "https://www.example.com/");
+        let (stream, _) = conn.into_parts();
+        assert!(stream.output.starts_with(b"CONNECT www.example.com:443 HTTP/1.1\r\n"));
+        let (stream, _) = connect(&connector, "http://www.example.com/").unwrap().into_parts();
+        assert!(stream.output.starts_with(b"CONNECT www.example.com:80 HTTP/1.1\r\n"));
+        assert!(connect(&connector, "http://www.example.org/").unwrap().into_parts().0.output.is_empty());
+        assert_eq!(*inner.dialed.lock().unwrap(), ["http://proxy.example.com:3128/", "http://proxy.example.com:3128/", "http://www.example.org/"]);
+        let resolver = Resolver::new().with_vars(vec![("all_proxy", "https://proxy.example.com:3128")]);
+        let connector = TunnelConnector { inner: MockConnector::default(), resolver };
+        match connect(&connector, "https://www.example.com/") {
+            Err(ConnectError::Io(e)) => assert_eq!(e.kind(), io::ErrorKind::Unsupported),
+            _ => panic!("HTTPS proxy must be unsupported"),
+        }
+    }
+}
@@ -0,0 +1,575 @@
+// Copyright (c) 2016 Ivan Nejgebauer <inejge@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::cell::RefCell;
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::hash::BuildHasher;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock};
+use std::time::{Duration, Instant};
+
+use arc_swap::{ArcSwap, Guard};
+use url::Url;
+
+use crate::target::Target;
+use crate::trace::{debug, warn};
+use crate::{default_resolver, redact_password, Error, ProxyUrl, ResolveOptions, Resolver, Selection};
+
+/// The key of a target in the decision cache: its scheme, host and port.
+type TargetKey = (String, String, Option<u16>);
+
+/// The number of independently locked shards of a decision cache, at most.
+const DECISION_SHARDS: usize = 16;
+
+/// A slot of a decision cache shard, linked into its recency list.
+#[derive(Debug)]
+struct Slot {
+    target: TargetKey,
+    proxy: ProxyUrl,
+    prev: usize,
+    next: usize,
+}
+
+/// The end of a recency list.
+const NIL: usize = usize::MAX;
+
+/// A shard of the decision cache: a map into slots kept in a list by recency of use, the most
+/// recently used first, so that both a lookup and an eviction take constant time.
+#[derive(Debug)]
+struct Shard {
+    capacity: usize,
+    index: HashMap<TargetKey, usize>,
+    slots: Vec<Slot>,
+    head: usize,
+    tail: usize,
+}
+
+impl Shard {
+    fn new(capacity: usize) -> Shard {
+        Shard { capacity, index: HashMap::new(), slots: Vec::new(), head: NIL, tail: NIL }
+    }
+
+    fn unlink(&mut self, n: usize) {
+        let (prev, next) = (self.slots[n].prev, self.slots[n].next);
+        match prev {
+            NIL => self.head = next,
+            prev => self.slots[prev].next = next,
+        }
+        match next {
+            NIL => self.tail = prev,
+            next => self.slots[next].prev = prev,
+        }
+    }
+
+    fn push_front(&mut self, n: usize) {
+        self.slots[n].prev = NIL;
+        self.slots[n].next = self.head;
+        match self.head {
+            NIL => self.tail = n,
+            head => self.slots[head].prev = n,
+        }
+        self.head = n;
+    }
+
+    fn get(&mut self, target: &TargetKey) -> Option<ProxyUrl> {
+        let n = *self.index.get(target)?;
+        if self.head != n {
+            self.unlink(n);
+            self.push_front(n);
+        }
+        Some(self.slots[n].proxy.clone())
+    }
+
+    fn insert(&mut self, target: TargetKey, proxy: ProxyUrl) {
+        if let Some(&n) = self.index.get(&target) {
+            self.slots[n].proxy = proxy;
+            self.unlink(n);
+            self.push_front(n);
+            return;
+        }
+        let n = if self.slots.len() < self.capacity {
+            self.slots.push(Slot { target: target.clone(), proxy, prev: NIL, next: NIL });
+            self.slots.len() - 1
+        } else {
+            let n = self.tail;
+            self.unlink(n);
+            let evicted = std::mem::replace(&mut self.slots[n], Slot { target: target.clone(), proxy, prev: NIL, next: NIL });
+            self.index.remove(&evicted.target);
+            n
+        };
+        self.index.insert(target, n);
+        self.push_front(n);
+    }
+
+    fn clear(&mut self) {
+        self.index.clear();
+        self.slots.clear();
+        self.head = NIL;
+        self.tail = NIL;
+    }
+}
+
+/// The decisions for recently resolved targets, the least recently used evicted first.
+///
+/// The targets are spread over several shards by their hash, each with its own lock and its
+/// share of the capacity, so that concurrent resolutions of different targets seldom contend.
+#[derive(Debug)]
+struct DecisionCache {
+    hasher: RandomState,
+    shards: Vec<Mutex<Shard>>,
+}
+
+impl DecisionCache {
+    fn new(capacity: usize) -> DecisionCache {
+        let count = capacity.min(DECISION_SHARDS);
+        let shards = (0..count)
+            .map(|n| Mutex::new(Shard::new(capacity / count + usize::from(n < capacity % count))))
+            .collect();
+        DecisionCache { hasher: RandomState::new(), shards }
+    }
+
+    fn shard(&self, target: &TargetKey) -> MutexGuard<'_, Shard> {
+        let n = self.hasher.hash_one(target) as usize % self.shards.len();
+        self.shards[n].lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn get(&self, target: &TargetKey) -> Option<ProxyUrl> {
+        self.shard(target).get(target)
+    }
+
+    fn insert(&self, target: TargetKey, proxy: ProxyUrl) {
+        self.shard(&target).insert(target, proxy);
+    }
+
+    fn clear(&self) {
+        for shard in &self.shards {
+            shard.lock().unwrap_or_else(|e| e.into_inner()).clear();
+        }
+    }
+}
+
+/// A snapshot of the environment, with the settings it was taken for, the time it was taken,
+/// and the decisions made from it.
+#[derive(Debug)]
+struct Snapshot {
+    base: Resolver,
+    resolver: Arc<Resolver>,
+    taken: Instant,
+    decisions: Option<DecisionCache>,
+}
+
+/// A resolver which works from a snapshot of the environment (requires the `cache` feature.)
+///
+/// A [`Resolver`](struct.Resolver.html) reads the proxy variables on each resolution, which
+/// lets it follow changes to the environment, but costs a lookup of every variable it consults.
+/// A `CachedResolver` takes a snapshot of the environment when it's created, and resolves from
+/// it as if the variables were given with [`Resolver::with_vars()`](struct.Resolver.html#method.with_vars),
+/// until the snapshot is retaken by [`refresh()`](#method.refresh) or, if a [TTL](#method.with_ttl)
+/// is set, by the first resolution after it expires. Resolutions load the current snapshot
+/// without locking, and continue with the one they started with if it's replaced meanwhile.
+///
+/// The settings of the resolver given to [`new()`](#method.new) apply to every snapshot, and
+/// the state shared by its clones, such as the history and the failure reports, survives a
+/// refresh; the settings can be replaced with [`reload()`](#method.reload). If the resolver
+/// doesn't read from the environment, e.g., because it was given a configuration, the snapshot
+/// is the resolver itself, and refreshing it has no effect.
+///
+/// ```
+/// # extern crate env_proxy;
+/// # extern crate url;
+/// use std::time::Duration;
+/// use env_proxy::{CachedResolver, Resolver};
+///
+/// # fn main() {
+/// let resolver = CachedResolver::new(Resolver::new()).with_ttl(Duration::from_secs(60));
+/// let url = url::Url::parse("https://www.example.com/").unwrap();
+/// if let Some(proxy) = resolver.for_url(&url).to_url() {
+///     println!("Proxy: {}", proxy);
+/// }
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct CachedResolver {
+    ttl: Option<Duration>,
+    capacity: usize,
+    current: ArcSwap<Snapshot>,
+    generation: AtomicU64,
+    refreshing: AtomicBool,
+}
+
+impl CachedResolver {
+    /// Create a cached resolver with the settings of the resolver, taking the first snapshot.
+    pub fn new(resolver: Resolver) -> CachedResolver {
+        CachedResolver {
+            ttl: None,
+            capacity: 0,
+            current: ArcSwap::from_pointee(Snapshot::take(resolver, 0)),
+            generation: AtomicU64::new(0),
+            refreshing: AtomicBool::new(false),
+        }
+    }
+
+    /// Retake the snapshot in the first resolution after it gets older than `ttl`.
+    ///
+    /// Without a TTL, which is the default, the snapshot is retaken only by [`refresh()`](#method.refresh).
+    /// The resolution which notices the expiry retakes the snapshot, while the concurrent ones
+    /// use the old one.
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    /// Remember the decisions for at most `capacity` targets, identified by their scheme, host
+    /// and port.
+    ///
+    /// A target which was resolved before is given the remembered proxy without consulting the
+    /// snapshot, which saves matching it against __no_proxy__ again, and isn't recorded in the
+    /// resolver's history. The targets are spread over up to 16 shards, locked separately and
+    /// each holding its share of the capacity; when a shard is full, the decision for its least
+    /// recently resolved target is forgotten. All decisions are forgotten when the snapshot is retaken or the
+    /// settings are reloaded, and when a proxy failure or success is reported.
+    ///
+    /// Since the decision is remembered per host, the cache shouldn't be used with a
+    /// [provider](struct.Resolver.html#method.with_providers) or a PAC script which decides
+    /// by the path of the target. It's bypassed if the resolver chooses among several candidates
+    /// with a [selection](enum.Selection.html) other than `First`. The cache is off by default;
+    /// a capacity of zero turns it off again.
+    pub fn with_decision_cache(mut self, capacity: usize) -> Self {
+        self.capacity = capacity;
+        self.refresh();
+        self
+    }
+
+    /// Retake the snapshot of the environment.
+    pub fn refresh(&self) {
+        let base = self.current.load().base.clone();
+        self.replace(Snapshot::take(base, self.capacity));
+    }
+
+    /// Replace the settings with those of the resolver, and take a new snapshot.
+    ///
+    /// The state shared by the clones of the previous resolver, such as the history, is replaced
+    /// by that of the new one.
+    pub fn reload(&self, resolver: Resolver) {
+        self.replace(Snapshot::take(resolver, self.capacity));
+    }
+
+    /// Publish the snapshot, and let the [local handles](#method.local) know of it.
+    fn replace(&self, snapshot: Snapshot) {
+        self.current.store(Arc::new(snapshot));
+        self.generation.fetch_add(1, Ordering::Release);
+    }
+
+    /// Return a handle which resolves from this thread's copy of the snapshot.
+    ///
+    /// See [`LocalResolver`](struct.LocalResolver.html).
+    pub fn local(&self) -> LocalResolver<'_> {
+        let generation = self.generation.load(Ordering::Acquire);
+        LocalResolver { cached: self, current: RefCell::new((generation, self.current.load_full())) }
+    }
+
+    /// Return the resolver working from the current snapshot, retaking it first if it's expired.
+    ///
+    /// The returned resolver keeps working from its snapshot after a refresh; it can be used
+    /// for the methods which `CachedResolver` doesn't provide itself.
+    pub fn resolver(&self) -> Arc<Resolver> {
+        self.snapshot().resolver.clone()
+    }
+
+    /// Return the current snapshot, retaking it first if it's expired.
+    fn snapshot(&self) -> Guard<Arc<Snapshot>> {
+        let current = self.current.load();
+        match self.ttl {
+            Some(ttl) if current.taken.elapsed() >= ttl && !self.refreshing.swap(true, Ordering::Acquire) => {
+                debug!("proxy settings snapshot expired");
+                self.refresh();
+                self.refreshing.store(false, Ordering::Release);
+                self.current.load()
+            },
+            _ => current,
+        }
+    }
+
+    /// Determine proxy parameters for a URL from the snapshot.
+    ///
+    /// See [`Resolver::for_url()`](struct.Resolver.html#method.for_url).
+    pub fn for_url(&self, url: &Url) -> ProxyUrl {
+        self.resolve(&Target::from_url(url))
+    }
+
+    /// Determine proxy parameters for a target given by its scheme, host and port, from the
+    /// snapshot.
+    ///
+    /// See [`Resolver::for_host()`](struct.Resolver.html#method.for_host).
+    pub fn for_host(&self, scheme: &str, host: &str, port: u16) -> ProxyUrl {
+        self.resolve(&Target::from_parts(scheme, host, port))
+    }
+
+    fn resolve(&self, target: &Target) -> ProxyUrl {
+        let current = self.snapshot();
+        let opts = ResolveOptions::default();
+        let decisions = match current.decisions {
+            Some(ref decisions) if current.resolver.selection == Selection::First => decisions,
+            _ => return current.resolver.resolve(target, &opts),
+        };
+        let key = (target.scheme().to_string(), target.host_str().unwrap_or("").to_string(), target.port_or_known_default());
+        if let Some(proxy) = decisions.get(&key) {
+            return proxy;
+        }
+        let proxy = current.resolver.resolve(target, &opts);
+        decisions.insert(key, proxy.clone());
+        proxy
+    }
+
+    /// Determine proxy parameters for a URL from the snapshot, reporting an unusable proxy
+    /// value as an error.
+    ///
+    /// See [`Resolver::try_for_url()`](struct.Resolver.html#method.try_for_url).
+    pub fn try_for_url(&self, url: &Url) -> Result<ProxyUrl, Error> {
+        self.resolver().try_for_url(url)
+    }
+
+    /// Determine proxy parameters for a URL given as a string, from the snapshot.
+    ///
+    /// See [`Resolver::for_url_str()`](struct.Resolver.html#method.for_url_str).
+    pub fn for_url_str<S: AsRef<str>>(&self, s: S) -> ProxyUrl {
+        let url = match Url::parse(s.as_ref()) {
+            Ok(url) => url,
+            Err(e) => {
+                warn!("error parsing '{}' as Url: {}", redact_password(s.as_ref()), e);
+                return ProxyUrl::none();
+            },
+        };
+        self.for_url(&url)
+    }
+
+    /// Return every proxy applicable to the URL from the snapshot.
+    ///
+    /// See [`Resolver::for_url_all()`](struct.Resolver.html#method.for_url_all).
+    pub fn for_url_all(&self, url: &Url) -> Vec<ProxyUrl> {
+        self.resolver().for_url_all(url)
+    }
+
+    /// Demote the proxy after a failure.
+    ///
+    /// See [`Resolver::report_failure()`](struct.Resolver.html#method.report_failure).
+    pub fn report_failure(&self, proxy: &ProxyUrl) {
+        let current = self.current.load();
+        current.base.report_failure(proxy);
+        current.forget_decisions();
+    }
+
+    /// Lift the demotion of the proxy.
+    ///
+    /// See [`Resolver::report_success()`](struct.Resolver.html#method.report_success).
+    pub fn report_success(&self, proxy: &ProxyUrl) {
+        let current = self.current.load();
+        current.base.report_success(proxy);
+        current.forget_decisions();
+    }
+}
+
+impl Snapshot {
+    fn take(base: Resolver, capacity: usize) -> Snapshot {
+        let resolver = base.snapshot();
+        let decisions = Some(capacity).filter(|&capacity| capacity > 0).map(DecisionCache::new);
+        Snapshot { base, resolver: Arc::new(resolver), taken: Instant::now(), decisions }
+    }
+
+    fn forget_decisions(&self) {
+        if let Some(ref decisions) = self.decisions {
+            decisions.clear();
+        }
+    }
+}
+
+/// A handle which resolves from its own copy of a [`CachedResolver`](struct.CachedResolver.html)'s
+/// snapshot (requires the `cache` feature.)
+///
+/// Loading the snapshot of a `CachedResolver` is lock-free, but it still writes to memory
+/// shared by all threads, which becomes a point of contention when many threads resolve at
+/// a high rate. A `LocalResolver` keeps the snapshot to itself, and checks only the resolver's
+/// generation counter, which changes when the snapshot is retaken or the settings are reloaded;
+/// the new snapshot is picked up by the first resolution after the change, or after the TTL
+/// expires, if one is set. The handle can't be shared among threads, and is meant to be kept
+/// by one, e.g., in a thread-local variable, as [`with_local_resolver()`](fn.with_local_resolver.html)
+/// does for the global resolver.
+///
+/// The [decision cache](struct.CachedResolver.html#method.with_decision_cache) is shared, so
+/// it isn't consulted by a local handle.
+#[derive(Debug)]
+pub struct LocalResolver<'a> {
+    cached: &'a CachedResolver,
+    current: RefCell<(u64, Arc<Snapshot>)>,
+}
+
+impl LocalResolver<'_> {
+    /// Replace the snapshot with the current one of the cached resolver, if it has changed
+    /// or expired.
+    ///
+    /// The snapshot is kept if it's in use by a resolution which has called back into the handle.
+    fn update(&self) {
+        let generation = self.cached.generation.load(Ordering::Acquire);
+        let mut current = match self.current.try_borrow_mut() {
+            Ok(current) => current,
+            Err(_) => return,
+        };
+        let expired = match self.cached.ttl {
+            Some(ttl) => current.1.taken.elapsed() >= ttl,
+            None => false,
+        };
+        if current.0 != generation || expired {
+            let snapshot = Guard::into_inner(self.cached.snapshot());
+            *current = (generation, snapshot);
+        }
+    }
+
+    /// Return the resolver working from the handle's snapshot, replacing it first if it has
+    /// changed or expired.
+    pub fn resolver(&self) -> Arc<Resolver> {
+        self.update();
+        self.current.borrow().1.resolver.clone()
+    }
+
+    /// Determine proxy parameters for a URL from the handle's snapshot.
+    ///
+    /// See [`Resolver::for_url()`](struct.Resolver.html#method.for_url).
+    pub fn for_url(&self, url: &Url) -> ProxyUrl {
+        self.resolve(&Target::from_url(url))
+    }
+
+    /// Determine proxy parameters for a target given by its scheme, host and port, from the
+    /// handle's snapshot.
+    ///
+    /// See [`Resolver::for_host()`](struct.Resolver.html#method.for_host).
+    pub fn for_host(&self, scheme: &str, host: &str, port: u16) -> ProxyUrl {
+        self.resolve(&Target::from_parts(scheme, host, port))
+    }
+
+    /// Determine proxy parameters for a URL given as a string, from the handle's snapshot.
+    ///
+    /// See [`Resolver::for_url_str()`](struct.Resolver.html#method.for_url_str).
+    pub fn for_url_str<S: AsRef<str>>(&self, s: S) -> ProxyUrl {
+        match Url::parse(s.as_ref()) {
+            Ok(url) => self.for_url(&url),
+            Err(e) => {
+                warn!("error parsing '{}' as Url: {}", redact_password(s.as_ref()), e);
+                ProxyUrl::none()
+            },
+        }
+    }
+
+    fn resolve(&self, target: &Target) -> ProxyUrl {
+        self.update();
+        self.current.borrow().1.resolver.resolve(target, &ResolveOptions::default())
+    }
+}
+
+/// Return the process-wide cached resolver (requires the `cache` feature.)
+///
+/// The resolver has the settings of the one used by [`for_url()`](fn.for_url.html), and takes
+/// its snapshot of the environment on first use. It's meant for long-running programs which
+/// resolve from many threads: each resolution loads the current snapshot without locking, and
+/// the snapshot changes only when [`reload_from_env()`](fn.reload_from_env.html) is called,
+/// so that a program can change the environment and then publish the change at once, instead
+/// of letting the resolutions race with `set_var()`.
+///
+/// ```
+/// # extern crate env_proxy;
+/// # extern crate url;
+/// # fn main() {
+/// let url = url::Url::parse("https://www.example.com/").unwrap();
+/// let proxy = env_proxy::global().for_url(&url);
+/// # let _ = proxy;
+/// # }
+/// ```
+pub fn global() -> &'static CachedResolver {
+    static GLOBAL: OnceLock<CachedResolver> = OnceLock::new();
+    GLOBAL.get_or_init(|| CachedResolver::new(default_resolver()))
+}
+
+/// Atomically replace the snapshot of the [global resolver](fn.global.html) with a fresh one
+/// (requires the `cache` feature.)
+///
+/// The settings are determined anew, as for [`for_url()`](fn.for_url.html), so that a change
+/// of the configuration file named by __ENV_PROXY_CONFIG__, if the `config-file` feature is
+/// enabled, is picked up as well. Resolutions in progress finish with the old snapshot.
+pub fn reload_from_env() {
+    global().reload(default_resolver());
+}
+
+thread_local! {
+    static LOCAL: LocalResolver<'static> = global().local();
+}
+
+/// Call the function with this thread's [handle](struct.LocalResolver.html) for the
+/// [global resolver](fn.global.html) (requires the `cache` feature.)
+///
+/// The handle is created on the thread's first call, and picks up the snapshots published by
+/// [`reload_from_env()`](fn.reload_from_env.html) without contending with other threads.
+///
+/// ```
+/// # extern crate env_proxy;
+/// # extern crate url;
+/// # fn main() {
+/// let url = url::Url::parse("https://www.example.com/").unwrap();
+/// let proxy = env_proxy::with_local_resolver(|resolver| resolver.for_url(&url));
+/// # let _ = proxy;
+/// # }
+/// ```
+pub fn with_local_resolver<F, R>(f: F) -> R
+    where F: FnOnce(&LocalResolver<'static>) -> R
+{
+    LOCAL.with(f)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DecisionCache, Shard, TargetKey};
+    use crate::ProxyUrl;
+
+    fn key(host: &str) -> TargetKey {
+        ("https".to_string(), host.to_string(), Some(443))
+    }
+
+    #[test]
+    fn shard_evicts_least_recently_used() {
+        let mut shard = Shard::new(2);
+        shard.insert(key("a"), ProxyUrl::none());
+        shard.insert(key("b"), ProxyUrl::none());
+        assert!(shard.get(&key("a")).is_some());
+        shard.insert(key("c"), ProxyUrl::none());
+        assert!(shard.get(&key("b")).is_none());
+        assert!(shard.get(&key("a")).is_some());
+        assert!(shard.get(&key("c")).is_some());
+        shard.insert(key("c"), ProxyUrl::none());
+        shard.insert(key("d"), ProxyUrl::none());
+        assert!(shard.get(&key("a")).is_none());
+        assert_eq!(shard.index.len(), 2);
+        assert_eq!(shard.slots.len(), 2);
+        shard.clear();
+        assert!(shard.get(&key("c")).is_none());
+    }
+
+    #[test]
+    fn capacity_spread_over_shards() {
+        let cache = DecisionCache::new(3);
+        assert_eq!(cache.shards.len(), 3);
+        let cache = DecisionCache::new(40);
+        let capacities = cache.shards.iter().map(|shard| shard.lock().unwrap().capacity).collect::<Vec<_>>();
+        assert_eq!(capacities.len(), 16);
+        assert_eq!(capacities.iter().sum::<usize>(), 40);
+        for n in 0..100 {
+            cache.insert(key(&n.to_string()), ProxyUrl::none());
+        }
+        assert!(cache.get(&key("99")).is_some());
+        assert!(cache.shards.iter().all(|shard| shard.lock().unwrap().index.len() <= 3));
+        cache.clear();
+        assert!(cache.get(&key("99")).is_none());
+    }
+}
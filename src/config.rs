@@ -0,0 +1,193 @@
+// Copyright (c) 2016 Ivan Nejgebauer <inejge@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::fmt;
+
+use url::Url;
+
+use crate::{matches_no_proxy, parse_proxy_value, redact_password, var_pair};
+use crate::{NoProxy, ProviderResult, ProxyProvider, Vars, DEFAULT_PROXY_PORT, DEFAULT_PROXY_SCHEME};
+
+/// The effective proxy configuration found in the environment.
+///
+/// A `ProxyConfig` collects the values of all variables consulted by [`for_url()`](fn.for_url.html)
+/// in canonical form: proxy values are parsed and canonicalized as described for
+/// [`ProxyUrl::to_url()`](struct.ProxyUrl.html#method.to_url), using the default port of 8080
+/// (values which can't be parsed are kept as-is), and the __no_proxy__ list is kept as a
+/// [`NoProxy`](struct.NoProxy.html). Which of the lowercase or uppercase variables held a value
+/// is not recorded, since it doesn't affect the outcome.
+///
+/// Equality, ordering and hashing are defined over the canonical form, so that two hosts with
+/// equivalent settings have equal configurations. For comparisons across processes, use
+/// [`fingerprint()`](#method.fingerprint).
+#[derive(Clone, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ProxyConfig {
+    pub(crate) http: Option<String>,
+    pub(crate) https: Option<String>,
+    pub(crate) ftp: Option<String>,
+    pub(crate) all: Option<String>,
+    pub(crate) no_proxy: NoProxy,
+}
+
+/// The offset basis of the 64-bit FNV-1a hash.
+pub(crate) const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+
+/// Continue the 64-bit FNV-1a hash with the bytes.
+pub(crate) fn fnv1a<I: IntoIterator<Item = u8>>(mut hash: u64, bytes: I) -> u64 {
+    for byte in bytes {
+        hash ^= u64::from(byte);
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    hash
+}
+
+fn canonical(var_value: Option<(&'static str, String)>) -> Option<String> {
+    var_value.map(|(var, raw)| match parse_proxy_value(var, &raw, DEFAULT_PROXY_SCHEME, Some(DEFAULT_PROXY_PORT), false) {
+        Ok(url) => url.into(),
+        Err(_) => raw,
+    })
+}
+
+impl fmt::Debug for ProxyConfig {
+    /// Format the configuration for debugging, with passwords replaced by asterisks.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let redacted = |value: &Option<String>| value.as_ref().map(|v| redact_password(v).into_owned());
+        f.debug_struct("ProxyConfig")
+            .field("http", &redacted(&self.http))
+            .field("https", &redacted(&self.https))
+            .field("ftp", &redacted(&self.ftp))
+            .field("all", &redacted(&self.all))
+            .field("no_proxy", &self.no_proxy)
+            .finish()
+    }
+}
+
+impl ProxyConfig {
+    /// Read the configuration from the environment.
+    pub fn from_env() -> ProxyConfig {
+        ProxyConfig::from_vars(None)
+    }
+
+    /// Read the configuration from the map, or from the environment if there's no map.
+    pub(crate) fn from_vars(vars: Option<&Vars>) -> ProxyConfig {
+        let canonical_var = |lc_var: &'static str, uc_var: &'static str| {
+            canonical(var_pair(vars, lc_var, uc_var))
+        };
+        ProxyConfig {
+            http: canonical_var("http_proxy", ""),
+            https: canonical_var("https_proxy", "HTTPS_PROXY"),
+            ftp: canonical_var("ftp_proxy", "FTP_PROXY"),
+            all: canonical_var("all_proxy", "ALL_PROXY"),
+            no_proxy: var_pair(vars, "no_proxy", "NO_PROXY").map(|(_, v)| NoProxy::parse(&v)).unwrap_or_default(),
+        }
+    }
+
+    /// Return the canonical value of __http_proxy__.
+    pub fn http_proxy(&self) -> Option<&str> {
+        self.http.as_deref()
+    }
+
+    /// Return the canonical value of __https_proxy__.
+    pub fn https_proxy(&self) -> Option<&str> {
+        self.https.as_deref()
+    }
+
+    /// Return the canonical value of __ftp_proxy__.
+    pub fn ftp_proxy(&self) -> Option<&str> {
+        self.ftp.as_deref()
+    }
+
+    /// Return the canonical value of __all_proxy__.
+    pub fn all_proxy(&self) -> Option<&str> {
+        self.all.as_deref()
+    }
+
+    /// Return the __no_proxy__ list.
+    pub fn no_proxy(&self) -> &NoProxy {
+        &self.no_proxy
+    }
+
+    /// Set the value of __http_proxy__, canonicalizing it as in [`from_env()`](#method.from_env).
+    pub fn set_http_proxy(&mut self, value: Option<&str>) {
+        self.http = canonical(value.map(|value| ("http_proxy", value.to_string())));
+    }
+
+    /// Set the value of __https_proxy__, canonicalizing it as in [`from_env()`](#method.from_env).
+    pub fn set_https_proxy(&mut self, value: Option<&str>) {
+        self.https = canonical(value.map(|value| ("https_proxy", value.to_string())));
+    }
+
+    /// Set the value of __ftp_proxy__, canonicalizing it as in [`from_env()`](#method.from_env).
+    pub fn set_ftp_proxy(&mut self, value: Option<&str>) {
+        self.ftp = canonical(value.map(|value| ("ftp_proxy", value.to_string())));
+    }
+
+    /// Set the value of __all_proxy__, canonicalizing it as in [`from_env()`](#method.from_env).
+    pub fn set_all_proxy(&mut self, value: Option<&str>) {
+        self.all = canonical(value.map(|value| ("all_proxy", value.to_string())));
+    }
+
+    /// Set the __no_proxy__ list.
+    pub fn set_no_proxy(&mut self, no_proxy: NoProxy) {
+        self.no_proxy = no_proxy;
+    }
+
+    /// Return a fingerprint of the configuration.
+    ///
+    /// Unlike the `Hash` implementation, whose results depend on the hasher, the fingerprint is
+    /// computed with a fixed algorithm (64-bit FNV-1a) over the canonical form, and is stable
+    /// across processes, platforms and versions of this crate. It can be used to detect drift
+    /// between the configurations of different hosts.
+    ///
+    /// The fingerprint is not a cryptographic hash, and credentials in the proxy values are
+    /// included in its input; don't publish it where guessing the credentials would be a concern.
+    pub fn fingerprint(&self) -> u64 {
+        let mut hash = FNV_OFFSET;
+        let fields = [
+            ("http_proxy", self.http.as_deref()),
+            ("https_proxy", self.https.as_deref()),
+            ("ftp_proxy", self.ftp.as_deref()),
+            ("all_proxy", self.all.as_deref()),
+        ];
+        let no_proxy = self.no_proxy.to_string();
+        let no_proxy = if self.no_proxy.is_empty() { None } else { Some(no_proxy.as_str()) };
+        for &(name, value) in fields.iter().chain([("no_proxy", no_proxy)].iter()) {
+            let value = match value {
+                Some(value) => value,
+                None => continue,
+            };
+            hash = fnv1a(hash, name.bytes().chain(Some(b'=')).chain(value.bytes()).chain(Some(b'\n')));
+        }
+        hash
+    }
+}
+
+impl ProxyProvider for ProxyConfig {
+    /// Return the proxies for the URL as the resolver would determine them from the configuration,
+    /// without name lookups for the __no_proxy__ check.
+    fn proxies_for(&self, url: &Url) -> ProviderResult {
+        if matches_no_proxy(url, &self.no_proxy, None) {
+            return ProviderResult::Direct;
+        }
+        let specific = match url.scheme() {
+            "https" => &self.https,
+            "http" => &self.http,
+            "ftp" => &self.ftp,
+            _ => &None,
+        };
+        let proxies = specific.iter().chain(self.all.iter()).cloned().collect::<Vec<_>>();
+        if proxies.is_empty() {
+            ProviderResult::Pass
+        } else {
+            ProviderResult::Proxies(proxies)
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        "env"
+    }
+}
@@ -0,0 +1,223 @@
+// Copyright (c) 2016 Ivan Nejgebauer <inejge@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::io;
+#[cfg(feature = "connect")]
+use std::net::TcpStream;
+#[cfg(feature = "tokio")]
+use std::time::Duration;
+
+use url::Url;
+
+#[cfg(feature = "tokio")]
+use crate::AsyncProxyStream;
+#[cfg(feature = "connect")]
+use crate::ProxyStream;
+use crate::{default_resolver, socks, tunnel, ProxyKind, Resolver};
+
+/// Return the host and port of a URL, with the host as used in an authority, and the port
+/// defaulted for the scheme.
+pub(crate) fn url_host_port(url: &Url) -> io::Result<(&str, u16)> {
+    let host = url.host_str().filter(|host| !host.is_empty());
+    match (host, url.port_or_known_default()) {
+        (Some(host), Some(port)) => Ok((host, port)),
+        _ => Err(io::Error::new(io::ErrorKind::InvalidInput, format!("no host or port in '{}'", url.origin().ascii_serialization()))),
+    }
+}
+
+/// Strip the brackets around an IPv6 address, for connecting.
+pub(crate) fn unbracketed(host: &str) -> &str {
+    host.trim_start_matches('[').trim_end_matches(']')
+}
+
+impl Resolver {
+    fn proxy_for_connect(&self, url: &Url) -> io::Result<Option<Url>> {
+        self.try_for_url(url)
+            .and_then(|proxy| proxy.try_to_url())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))
+    }
+
+    /// Connect to the target host and port, through the proxy determined for the URL.
+    ///
+    /// The URL is used only for resolution. With an HTTP proxy, a `CONNECT` tunnel is opened,
+    /// over TLS to an HTTPS proxy; with a SOCKS proxy, the proxy is asked to connect.
+    #[cfg(feature = "connect")]
+    pub(crate) fn connect_via(&self, url: &Url, host: &str, port: u16) -> io::Result<ProxyStream> {
+        let proxy = match self.proxy_for_connect(url)? {
+            Some(proxy) => proxy,
+            None => return TcpStream::connect((unbracketed(host), port)).map(ProxyStream::tcp),
+        };
+        let (proxy_host, proxy_port) = url_host_port(&proxy)?;
+        match ProxyKind::from_scheme(proxy.scheme()) {
+            Some(ProxyKind::Http) => {
+                let mut stream = TcpStream::connect((unbracketed(proxy_host), proxy_port))?;
+                tunnel::tunnel(&mut stream, &proxy, &format!("{}:{}", host, port))?;
+                Ok(ProxyStream::tcp(stream))
+            },
+            #[cfg(feature = "rustls")]
+            Some(ProxyKind::Https) => {
+                let stream = TcpStream::connect((unbracketed(proxy_host), proxy_port))?;
+                let mut stream = self.proxy_tls_handshake(stream, proxy_host)?;
+                tunnel::tunnel(&mut stream, &proxy, &format!("{}:{}", host, port))?;
+                Ok(ProxyStream::tls(stream))
+            },
+            Some(kind) if kind != ProxyKind::Https => {
+                let mut stream = TcpStream::connect((unbracketed(proxy_host), proxy_port))?;
+                socks::handshake(&mut stream, &proxy, host, port)?;
+                Ok(ProxyStream::tcp(stream))
+            },
+            _ => Err(io::Error::new(io::ErrorKind::Unsupported, format!("unsupported proxy scheme '{}'", proxy.scheme()))),
+        }
+    }
+
+    /// Open a connection to the host and port of the URL, through the proxy determined for it
+    /// (requires the `connect` feature.)
+    ///
+    /// The proxy is determined by [`for_url()`](#method.for_url). With an HTTP proxy, a `CONNECT`
+    /// tunnel to the target is opened, authorized with the proxy credentials, if any. The target
+    /// is tunneled even for an `http` URL, since the stream is meant for arbitrary data, and not
+    /// only for requests in absolute form; note that some proxies only allow tunnels to port 443.
+    /// With an HTTPS proxy, the tunnel is opened over TLS to the proxy, which requires the
+    /// `rustls` feature; see [`with_proxy_tls()`](#method.with_proxy_tls) for the ways to verify
+    /// the proxy, and [`with_proxy_server_name()`](#method.with_proxy_server_name) for the name
+    /// it's verified for. Without the feature, HTTPS proxies give an error of the `Unsupported`
+    /// kind. With a SOCKS proxy, the proxy is asked to connect to the target. If the target
+    /// should be connected to directly, it is.
+    ///
+    /// The returned stream is ready for TLS, for an `https` URL, or for the application protocol.
+    /// An unusable proxy value is reported as an error of the `InvalidInput` kind, and a failed
+    /// TLS handshake with the proxy as an error of the `InvalidData` kind.
+    #[cfg(feature = "connect")]
+    pub fn connect(&self, url: &Url) -> io::Result<ProxyStream> {
+        let (host, port) = url_host_port(url)?;
+        self.connect_via(url, host, port)
+    }
+
+    /// Connect to the target host and port through the proxy determined for the URL, over a
+    /// __tokio__ connection.
+    ///
+    /// See [`connect_via()`](#method.connect_via); TLS to an HTTPS proxy requires the
+    /// `tokio-rustls` feature.
+    #[cfg(feature = "tokio")]
+    pub(crate) async fn connect_via_tokio(&self, url: &Url, host: &str, port: u16) -> io::Result<AsyncProxyStream> {
+        use tokio::net::TcpStream;
+
+        let proxy = match self.proxy_for_connect(url)? {
+            Some(proxy) => proxy,
+            None => return TcpStream::connect((unbracketed(host), port)).await.map(AsyncProxyStream::tcp),
+        };
+        let (proxy_host, proxy_port) = url_host_port(&proxy)?;
+        match ProxyKind::from_scheme(proxy.scheme()) {
+            Some(ProxyKind::Http) => {
+                let mut stream = TcpStream::connect((unbracketed(proxy_host), proxy_port)).await?;
+                tunnel::tunnel_tokio(&mut stream, &proxy, &format!("{}:{}", host, port)).await?;
+                Ok(AsyncProxyStream::tcp(stream))
+            },
+            #[cfg(feature = "tokio-rustls")]
+            Some(ProxyKind::Https) => {
+                let stream = TcpStream::connect((unbracketed(proxy_host), proxy_port)).await?;
+                let mut stream = self.proxy_tls_handshake_tokio(stream, proxy_host).await?;
+                tunnel::tunnel_tokio(&mut stream, &proxy, &format!("{}:{}", host, port)).await?;
+                Ok(AsyncProxyStream::tls(stream))
+            },
+            Some(kind) if kind != ProxyKind::Https => {
+                let mut stream = TcpStream::connect((unbracketed(proxy_host), proxy_port)).await?;
+                socks::handshake_tokio(&mut stream, &proxy, host, port).await?;
+                Ok(AsyncProxyStream::tcp(stream))
+            },
+            _ => Err(io::Error::new(io::ErrorKind::Unsupported, format!("unsupported proxy scheme '{}'", proxy.scheme()))),
+        }
+    }
+
+    /// Open a __tokio__ connection to the host and port of the URL, through the proxy determined
+    /// for it (requires the `tokio` feature.)
+    ///
+    /// The connection is opened as by [`connect()`](#method.connect), without blocking: the
+    /// status line of the proxy's response to `CONNECT` is checked, and a SOCKS proxy is asked
+    /// to connect, resolving the target's name if the scheme calls for it. TLS to an HTTPS proxy
+    /// requires the `tokio-rustls` feature.
+    #[cfg(feature = "tokio")]
+    pub async fn connect_async(&self, url: &Url) -> io::Result<AsyncProxyStream> {
+        let (host, port) = url_host_port(url)?;
+        self.connect_via_tokio(url, host, port).await
+    }
+
+    /// Open a __tokio__ connection to the host and port of the URL, through the proxy determined
+    /// for it, giving up after the timeout (requires the `tokio` feature.)
+    ///
+    /// See [`connect_async()`](#method.connect_async). The timeout covers the whole of opening
+    /// the connection, including the handshake with the proxy; when it expires, an error of the
+    /// `TimedOut` kind is returned. The runtime must have the time driver enabled.
+    #[cfg(feature = "tokio")]
+    pub async fn connect_async_timeout(&self, url: &Url, timeout: Duration) -> io::Result<AsyncProxyStream> {
+        match tokio::time::timeout(timeout, self.connect_async(url)).await {
+            Ok(result) => result,
+            Err(_) => Err(io::Error::new(io::ErrorKind::TimedOut, format!("connection to '{}' timed out", url.origin().ascii_serialization()))),
+        }
+    }
+}
+
+/// Open a connection to the host and port of the URL, through the proxy from the environment
+/// (requires the `connect` feature.)
+///
+/// The proxy is determined with the resolver of [`for_url()`](fn.for_url.html), as described
+/// for [`Resolver::connect()`](struct.Resolver.html#method.connect).
+///
+/// ```no_run
+/// # extern crate env_proxy;
+/// # extern crate url;
+/// # use std::io::{Read, Write};
+/// # fn main() -> std::io::Result<()> {
+/// let url = url::Url::parse("http://www.example.org/").unwrap();
+/// let mut stream = env_proxy::connect(&url)?;
+/// stream.write_all(b"GET / HTTP/1.1\r\nHost: www.example.org\r\nConnection: close\r\n\r\n")?;
+/// let mut response = String::new();
+/// stream.read_to_string(&mut response)?;
+/// # Ok(())
+/// # }
+/// ```
+#[cfg(feature = "connect")]
+pub fn connect(url: &Url) -> io::Result<ProxyStream> {
+    default_resolver().connect(url)
+}
+
+/// Open a __tokio__ connection to the host and port of the URL, through the proxy from the
+/// environment (requires the `tokio` feature.)
+///
+/// The proxy is determined with the resolver of [`for_url()`](fn.for_url.html), as described
+/// for [`Resolver::connect_async()`](struct.Resolver.html#method.connect_async).
+///
+/// ```no_run
+/// # extern crate env_proxy;
+/// # extern crate tokio;
+/// # extern crate url;
+/// # use std::time::Duration;
+/// # use tokio::io::{AsyncReadExt, AsyncWriteExt};
+/// # async fn get() -> std::io::Result<()> {
+/// let url = url::Url::parse("http://www.example.org/").unwrap();
+/// let mut stream = env_proxy::connect_async_timeout(&url, Duration::from_secs(10)).await?;
+/// stream.write_all(b"GET / HTTP/1.1\r\nHost: www.example.org\r\nConnection: close\r\n\r\n").await?;
+/// let mut response = String::new();
+/// stream.read_to_string(&mut response).await?;
+/// # Ok(())
+/// # }
+/// # fn main() {}
+/// ```
+#[cfg(feature = "tokio")]
+pub async fn connect_async(url: &Url) -> io::Result<AsyncProxyStream> {
+    default_resolver().connect_async(url).await
+}
+
+/// Open a __tokio__ connection to the host and port of the URL, through the proxy from the
+/// environment, giving up after the timeout (requires the `tokio` feature.)
+///
+/// The proxy is determined with the resolver of [`for_url()`](fn.for_url.html), as described
+/// for [`Resolver::connect_async_timeout()`](struct.Resolver.html#method.connect_async_timeout).
+#[cfg(feature = "tokio")]
+pub async fn connect_async_timeout(url: &Url, timeout: Duration) -> io::Result<AsyncProxyStream> {
+    default_resolver().connect_async_timeout(url, timeout).await
+}
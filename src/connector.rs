@@ -0,0 +1,432 @@
+// Copyright (c) 2016 Ivan Nejgebauer <inejge@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! A proxy-aware connector for __hyper__ (requires the `hyper-connector` feature.)
+//!
+//! [`ProxyConnector`](struct.ProxyConnector.html) wraps a connector which dials TCP connections,
+//! such as `hyper_util::client::legacy::connect::HttpConnector`, and consults a resolver for each
+//! request URI. A target without a proxy is dialed directly. For a plain `http` target, the
+//! connection goes to the proxy, and is marked as proxied, so that the client sends requests in
+//! the absolute form the proxy expects. For other targets, a `CONNECT` tunnel to the target is
+//! opened through the proxy, as described in the [`tunnel`](../tunnel/index.html) module, and
+//! the client, or a TLS connector wrapping the `ProxyConnector`, uses it as a direct connection.
+//!
+//! The connection to the proxy is made by the wrapped connector with the proxy's scheme, so an
+//! `https` proxy needs a wrapped connector which can establish TLS. With a SOCKS proxy, the
+//! connector dials the proxy and asks it to connect to the target, for any target, as described
+//! in the [`socks`](../socks/index.html) module; note that the `socks4` and `socks5` schemes have
+//! the target's name resolved on the thread polling the connection.
+//!
+//! A proxy which forwards requests gets the proxy credentials only from the requests themselves,
+//! which the connector can't change; use [`authorize()`](struct.ProxyConnector.html#method.authorize)
+//! on each request sent through the client. With the `hyper-legacy` feature,
+//! [`http_connector()`](fn.http_connector.html) sets up a connector for __hyper-util__'s legacy
+//! client:
+//!
+//! ```no_run
+//! # extern crate env_proxy;
+//! # extern crate http;
+//! # extern crate hyper_util;
+//! # #[cfg(feature = "hyper-legacy")]
+//! # async fn get() -> Result<(), Box<dyn std::error::Error>> {
+//! use hyper_util::client::legacy::Client;
+//! use hyper_util::rt::TokioExecutor;
+//!
+//! let connector = env_proxy::connector::http_connector();
+//! let client = Client::builder(TokioExecutor::new()).build::<_, String>(connector.clone());
+//! let mut request = http::Request::get("http://www.example.com/").body(String::new())?;
+//! connector.authorize(&mut request);
+//! let response = client.request(request).await?;
+//! println!("{}", response.status());
+//! # Ok(())
+//! # }
+//! # fn main() {}
+//! ```
+
+use std::error::Error as StdError;
+use std::future::{poll_fn, Future};
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use http::header::{HeaderValue, PROXY_AUTHORIZATION};
+use http::{Request, Uri};
+use hyper::rt::{Read, ReadBuf, ReadBufCursor, Write};
+use hyper_util::client::legacy::connect::{Connected, Connection};
+use tower_service::Service;
+use url::Url;
+
+use crate::socks::{socks_kind, Handshake, TargetAddr};
+use crate::tunnel::{connect_request, check_connect_response, proxy_authorization, unexpected_eof, HeaderReader};
+use crate::{default_resolver, DialMode, ProxyKind, Resolver};
+
+/// The future returned by [`ProxyConnector`](struct.ProxyConnector.html).
+pub type ProxyConnecting<T> = Pin<Box<dyn Future<Output = io::Result<ProxyStream<T>>> + Send>>;
+
+/// A connector which routes connections through the proxies from the environment.
+///
+/// See the [module documentation](index.html) for the details.
+#[derive(Clone, Debug)]
+pub struct ProxyConnector<C> {
+    inner: C,
+    resolver: Resolver,
+    tunnel_all: bool,
+}
+
+impl<C> ProxyConnector<C> {
+    /// Wrap the connector, using the resolver of [`for_url()`](../fn.for_url.html).
+    pub fn new(inner: C) -> ProxyConnector<C> {
+        ProxyConnector::with_resolver(inner, default_resolver())
+    }
+
+    /// Wrap the connector, using the given resolver.
+    pub fn with_resolver(inner: C, resolver: Resolver) -> ProxyConnector<C> {
+        ProxyConnector { inner, resolver, tunnel_all: false }
+    }
+
+    /// Open a `CONNECT` tunnel through the proxy for plain `http` targets too, instead of
+    /// forwarding the requests.
+    ///
+    /// This is needed for protocols which a forwarding proxy can't relay, such as HTTP/2 over
+    /// cleartext used by gRPC.
+    pub fn with_tunnel_all(mut self, tunnel_all: bool) -> Self {
+        self.tunnel_all = tunnel_all;
+        self
+    }
+
+    /// Return the resolver.
+    pub fn resolver(&self) -> &Resolver {
+        &self.resolver
+    }
+
+    /// Return the wrapped connector.
+    pub fn inner(&self) -> &C {
+        &self.inner
+    }
+
+    /// Add the `Proxy-Authorization` header to a request which will be forwarded by a proxy.
+    ///
+    /// If the request's target is a plain `http` URI, the connector doesn't
+    /// [tunnel](#method.with_tunnel_all) such targets, and the proxy has credentials, the header
+    /// is set from them, using the Basic scheme; otherwise, the request is left alone. A request
+    /// which already has the header keeps it.
+    pub fn authorize<B>(&self, request: &mut Request<B>) {
+        if request.headers().contains_key(PROXY_AUTHORIZATION) || request.uri().scheme_str() != Some("http") || self.tunnel_all {
+            return;
+        }
+        let proxy = match Url::parse(&request.uri().to_string()).ok().and_then(|target| self.resolver.for_url(&target).to_url()) {
+            Some(proxy) if proxy.scheme() == "http" || proxy.scheme() == "https" => proxy,
+            _ => return,
+        };
+        if let Some(value) = proxy_authorization(&proxy).and_then(|value| HeaderValue::from_str(&value).ok()) {
+            request.headers_mut().insert(PROXY_AUTHORIZATION, value);
+        }
+    }
+}
+
+/// Return a connector for __hyper-util__'s legacy client, using the resolver of
+/// [`for_url()`](../fn.for_url.html) (requires the `hyper-legacy` feature.)
+///
+/// The wrapped connector is an `HttpConnector` which also accepts URIs other than `http`, so that
+/// a TLS connector can be layered on top of the `ProxyConnector`.
+#[cfg(feature = "hyper-legacy")]
+pub fn http_connector() -> ProxyConnector<hyper_util::client::legacy::connect::HttpConnector> {
+    let mut inner = hyper_util::client::legacy::connect::HttpConnector::new();
+    inner.enforce_http(false);
+    ProxyConnector::new(inner)
+}
+
+/// A connection made by a [`ProxyConnector`](struct.ProxyConnector.html).
+#[derive(Debug)]
+pub struct ProxyStream<T> {
+    inner: T,
+    forwarding: bool,
+}
+
+impl<T> ProxyStream<T> {
+    /// Return `true` if the connection is to a proxy which forwards the requests, as opposed to
+    /// a direct or tunneled connection to the target.
+    pub fn is_forwarding(&self) -> bool {
+        self.forwarding
+    }
+
+    /// Return the underlying connection.
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<T: Read + Unpin> Read for ProxyStream<T> {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context, buf: ReadBufCursor) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_read(cx, buf)
+    }
+}
+
+impl<T: Write + Unpin> Write for ProxyStream<T> {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context, buf: &[u8]) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.get_mut().inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_shutdown(cx)
+    }
+}
+
+impl<T: Connection> Connection for ProxyStream<T> {
+    fn connected(&self) -> Connected {
+        self.inner.connected().proxy(self.forwarding)
+    }
+}
+
+/// Write all of the data to the stream, and flush it.
+async fn write_all<T: Write + Unpin>(stream: &mut T, mut data: &[u8]) -> io::Result<()> {
+    while !data.is_empty() {
+        let n = poll_fn(|cx| Pin::new(&mut *stream).poll_write(cx, data)).await?;
+        if n == 0 {
+            return Err(io::Error::new(io::ErrorKind::WriteZero, "failed to write to the proxy"));
+        }
+        data = &data[n..];
+    }
+    poll_fn(|cx| Pin::new(&mut *stream).poll_flush(cx)).await
+}
+
+/// Read from the stream into the buffer, returning the number of bytes read.
+async fn read<T: Read + Unpin>(stream: &mut T, buf: &mut [u8]) -> io::Result<usize> {
+    poll_fn(|cx| {
+        let mut buf = ReadBuf::new(&mut *buf);
+        match Pin::new(&mut *stream).poll_read(cx, buf.unfilled()) {
+            Poll::Ready(Ok(())) => Poll::Ready(Ok(buf.filled().len())),
+            Poll::Ready(Err(e)) => Poll::Ready(Err(e)),
+            Poll::Pending => Poll::Pending,
+        }
+    }).await
+}
+
+/// Open a tunnel to the target authority over a connection to the proxy.
+async fn tunnel<T: Read + Write + Unpin>(stream: &mut T, proxy: &Url, authority: &str) -> io::Result<()> {
+    write_all(stream, connect_request(proxy, authority).as_bytes()).await?;
+    let mut reader = HeaderReader::default();
+    let mut byte = [0u8];
+    loop {
+        if read(stream, &mut byte).await? == 0 {
+            return Err(unexpected_eof());
+        }
+        if reader.push(byte[0])? {
+            return check_connect_response(&reader.header);
+        }
+    }
+}
+
+/// Ask the SOCKS proxy to connect to the target host and port over a connection to the proxy.
+async fn socks_handshake<T: Read + Write + Unpin>(stream: &mut T, proxy: &Url, host: &str, port: u16) -> io::Result<()> {
+    let addr = TargetAddr::for_kind(socks_kind(proxy)?, host, port)?;
+    let mut handshake = Handshake::new(proxy, addr, port)?;
+    let mut reply = Vec::new();
+    while let Some((message, len)) = handshake.step(&reply)? {
+        if !message.is_empty() {
+            write_all(stream, &message).await?;
+        }
+        reply.resize(len, 0);
+        let mut filled = 0;
+        while filled < len {
+            match read(stream, &mut reply[filled..]).await? {
+                0 => return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "proxy closed the connection during the SOCKS handshake")),
+                n => filled += n,
+            }
+        }
+    }
+    Ok(())
+}
+
+fn other_error<E: Into<Box<dyn StdError + Send + Sync>>>(e: E) -> io::Error {
+    io::Error::other(e)
+}
+
+impl<C> Service<Uri> for ProxyConnector<C>
+    where C: Service<Uri> + Clone + Send + 'static,
+          C::Response: Read + Write + Connection + Unpin + Send + 'static,
+          C::Future: Send + 'static,
+          C::Error: Into<Box<dyn StdError + Send + Sync>>
+{
+    type Response = ProxyStream<C::Response>;
+    type Error = io::Error;
+    type Future = ProxyConnecting<C::Response>;
+
+    fn poll_ready(&mut self, cx: &mut Context) -> Poll<io::Result<()>> {
+        self.inner.poll_ready(cx).map_err(other_error)
+    }
+
+    fn call(&mut self, uri: Uri) -> Self::Future {
+        let target = Url::parse(&uri.to_string()).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e));
+        let proxy = target.as_ref().ok().map(|target| self.resolver.try_for_url(target).and_then(|proxy| proxy.try_to_url()));
+        // Take the instance which was polled ready, leaving a fresh clone in its place.
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let tunnel_all = self.tunnel_all;
+        Box::pin(async move {
+            let target = target?;
+            let proxy = match proxy {
+                Some(Ok(proxy)) => proxy,
+                Some(Err(e)) => return Err(io::Error::new(io::ErrorKind::InvalidInput, e)),
+                None => None,
+            };
+            let proxy = match proxy {
+                None => {
+                    let inner = inner.call(uri).await.map_err(other_error)?;
+                    return Ok(ProxyStream { inner, forwarding: false });
+                },
+                Some(proxy) => proxy,
+            };
+            let mode = match ProxyKind::from_scheme(proxy.scheme()) {
+                Some(kind) => kind.dial_mode(target.scheme()),
+                None => return Err(io::Error::new(io::ErrorKind::Unsupported, format!("unsupported proxy scheme '{}'", proxy.scheme()))),
+            };
+            let host = proxy.host_str().unwrap_or("");
+            let port = proxy.port_or_known_default().unwrap_or(0);
+            let dial_scheme = if mode == DialMode::Socks { "http" } else { proxy.scheme() };
+            let proxy_uri = format!("{}://{}:{}", dial_scheme, host, port).parse::<Uri>()
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+            let mut stream = inner.call(proxy_uri).await.map_err(other_error)?;
+            match mode {
+                DialMode::Socks => {
+                    let port = target.port_or_known_default().unwrap_or(0);
+                    socks_handshake(&mut stream, &proxy, target.host_str().unwrap_or(""), port).await?;
+                    return Ok(ProxyStream { inner: stream, forwarding: false });
+                },
+                DialMode::Forward if !tunnel_all => return Ok(ProxyStream { inner: stream, forwarding: true }),
+                _ => (),
+            }
+            let authority = format!("{}:{}", target.host_str().unwrap_or(""), target.port_or_known_default().unwrap_or(0));
+            tunnel(&mut stream, &proxy, &authority).await?;
+            Ok(ProxyStream { inner: stream, forwarding: false })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::future::{ready, Ready};
+    use std::io::{self, Read as _, Write as _};
+    use std::pin::Pin;
+    use std::sync::{Arc, Mutex};
+    use std::task::{Context, Poll, Waker};
+
+    use http::Uri;
+    use hyper::rt::{Read, ReadBufCursor, Write};
+    use hyper_util::client::legacy::connect::{Connected, Connection};
+    use tower_service::Service;
+
+    use super::ProxyConnector;
+    use crate::tunnel::tests::MockStream;
+    use crate::Resolver;
+
+    impl Read for MockStream {
+        fn poll_read(self: Pin<&mut Self>, _cx: &mut Context, mut buf: ReadBufCursor) -> Poll<io::Result<()>> {
+            let mut byte = [0u8];
+            let n = self.get_mut().read(&mut byte)?;
+            buf.put_slice(&byte[..n]);
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    impl Write for MockStream {
+        fn poll_write(self: Pin<&mut Self>, _cx: &mut Context, buf: &[u8]) -> Poll<io::Result<usize>> {
+            Poll::Ready(self.get_mut().write(buf))
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    impl Connection for MockStream {
+        fn connected(&self) -> Connected {
+            Connected::new()
+        }
+    }
+
+    /// A connector which records the dialed URIs and returns mock streams.
+    #[derive(Clone)]
+    struct MockConnector {
+        dialed: Arc<Mutex<Vec<String>>>,
+        reply: &'static [u8],
+    }
+
+    impl Default for MockConnector {
+        fn default() -> MockConnector {
+            MockConnector { dialed: Arc::default(), reply: b"HTTP/1.1 200 Connection established\r\n\r\n" }
+        }
+    }
+
+    impl Service<Uri> for MockConnector {
+        type Response = MockStream;
+        type Error = io::Error;
+        type Future = Ready<io::Result<MockStream>>;
+
+        fn poll_ready(&mut self, _cx: &mut Context) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, uri: Uri) -> Self::Future {
+            self.dialed.lock().unwrap().push(uri.to_string());
+            ready(Ok(MockStream::new(self.reply)))
+        }
+    }
+
+    fn connect(connector: &mut ProxyConnector<MockConnector>, uri: &str) -> io::Result<super::ProxyStream<MockStream>> {
+        let mut fut = connector.call(uri.parse().unwrap());
+        match fut.as_mut().poll(&mut Context::from_waker(Waker::noop())) {
+            Poll::Ready(res) => res,
+            Poll::Pending => panic!("mock connector is never pending"),
+        }
+    }
+
+    #[test]
+    fn proxy_connector() {
+        let resolver = Resolver::new().with_vars(vec![("all_proxy", "user:secret@proxy.example.com:3128"), ("no_proxy", "example.org")]);
+        let inner = MockConnector::default();
+        let mut connector = ProxyConnector::with_resolver(inner.clone(), resolver);
+        let stream = connect(&mut connector, "https://www.example.com/").unwrap();
+        assert!(!stream.is_forwarding());
+        assert!(stream.into_inner().output.starts_with(b"CONNECT www.example.com:443 HTTP/1.1\r\n"));
+        let stream = connect(&mut connector, "http://www.example.com/").unwrap();
+        assert!(stream.is_forwarding() && stream.connected().is_proxied());
+        assert!(!connect(&mut connector, "http://www.example.org/").unwrap().is_forwarding());
+        assert_eq!(*inner.dialed.lock().unwrap(), ["http://proxy.example.com:3128/", "http://proxy.example.com:3128/", "http://www.example.org/"]);
+        let mut request = http::Request::get("http://www.example.com/").body(()).unwrap();
+        connector.authorize(&mut request);
+        assert_eq!(request.headers()[http::header::PROXY_AUTHORIZATION], "Basic dXNlcjpzZWNyZXQ=");
+        let mut request = http::Request::get("https://www.example.com/").body(()).unwrap();
+        connector.authorize(&mut request);
+        assert!(request.headers().is_empty());
+        let mut connector = connector.with_tunnel_all(true);
+        let stream = connect(&mut connector, "http://grpc.example.com:50051/").unwrap();
+        assert!(!stream.is_forwarding());
+        assert!(stream.into_inner().output.starts_with(b"CONNECT grpc.example.com:50051 HTTP/1.1\r\n"));
+        let mut request = http::Request::get("http://www.example.com/").body(()).unwrap();
+        connector.authorize(&mut request);
+        assert!(request.headers().is_empty());
+        let resolver = Resolver::new().with_vars(vec![("all_proxy", "socks5h://localhost:9050")]);
+        let inner = MockConnector { reply: b"\x05\x00\x05\x00\x00\x01\x7f\x00\x00\x01\x04\x38", ..MockConnector::default() };
+        let mut connector = ProxyConnector::with_resolver(inner.clone(), resolver);
+        let stream = connect(&mut connector, "http://www.example.com/").unwrap();
+        assert!(!stream.is_forwarding());
+        assert_eq!(stream.into_inner().output, b"\x05\x01\x00\x05\x01\x00\x03\x0fwww.example.com\x00\x50");
+        assert_eq!(*inner.dialed.lock().unwrap(), ["http://localhost:9050/"]);
+        let resolver = Resolver::new().with_vars(vec![("all_proxy", "ftp://proxy.example.com:2121")]);
+        let mut connector = ProxyConnector::with_resolver(MockConnector::default(), resolver);
+        assert_eq!(connect(&mut connector, "https://www.example.com/").unwrap_err().kind(), io::ErrorKind::Unsupported);
+    }
+}
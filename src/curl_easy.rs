@@ -0,0 +1,60 @@
+// Copyright (c) 2016 Ivan Nejgebauer <inejge@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use curl::easy::Easy;
+use percent_encoding::percent_decode_str;
+use url::Url;
+
+use crate::{default_resolver, Resolver};
+
+impl Resolver {
+    /// Set the proxy options of a curl handle for the target URL (requires the `curl` feature.)
+    ///
+    /// `CURLOPT_PROXY` is set to the proxy determined for the target by [`for_url()`](#method.for_url),
+    /// without credentials, which are set, decoded, with `CURLOPT_PROXYUSERNAME` and
+    /// `CURLOPT_PROXYPASSWORD`. If the target should be connected to directly, `CURLOPT_PROXY` is
+    /// set to an empty string, which keeps libcurl from reading the environment. `CURLOPT_NOPROXY`
+    /// is set to the resolver's [effective __no_proxy__ list](#method.effective_no_proxy),
+    /// translated as libcurl understands it, for the case that the handle is reused for other
+    /// targets; the decision for the given target is always this crate's. A proxy value which can't
+    /// be used is logged, and the proxy is disabled.
+    pub fn apply_to_curl(&self, easy: &mut Easy, target: &Url) -> Result<(), curl::Error> {
+        easy.noproxy(&self.effective_no_proxy().curl_entries().join(","))?;
+        let mut proxy = match self.for_url(target).to_url() {
+            Some(proxy) => proxy,
+            None => return easy.proxy(""),
+        };
+        if !proxy.username().is_empty() {
+            easy.proxy_username(&percent_decode_str(proxy.username()).decode_utf8_lossy())?;
+            easy.proxy_password(&proxy.password().map(|p| percent_decode_str(p).decode_utf8_lossy()).unwrap_or_default())?;
+            proxy.set_username("").unwrap_or(());
+            proxy.set_password(None).unwrap_or(());
+        }
+        easy.proxy(proxy.as_str())
+    }
+}
+
+/// Set the proxy options of a curl handle for the target URL (requires the `curl` feature.)
+///
+/// The proxy is determined with the resolver of [`for_url()`](fn.for_url.html), as described
+/// for [`Resolver::apply_to_curl()`](struct.Resolver.html#method.apply_to_curl).
+///
+/// ```no_run
+/// # extern crate curl;
+/// # extern crate env_proxy;
+/// # extern crate url;
+/// # fn main() -> Result<(), curl::Error> {
+/// let url = url::Url::parse("https://www.example.com/").unwrap();
+/// let mut easy = curl::easy::Easy::new();
+/// easy.url(url.as_str())?;
+/// env_proxy::apply_to_curl(&mut easy, &url)?;
+/// easy.perform()
+/// # }
+/// ```
+pub fn apply_to_curl(easy: &mut Easy, target: &Url) -> Result<(), curl::Error> {
+    default_resolver().apply_to_curl(easy, target)
+}
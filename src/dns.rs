@@ -0,0 +1,68 @@
+// Copyright (c) 2016 Ivan Nejgebauer <inejge@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::fmt;
+use std::future::Future;
+use std::io;
+use std::net::{IpAddr, ToSocketAddrs};
+use std::pin::Pin;
+use std::sync::Arc;
+
+/// A synchronous DNS resolver.
+///
+/// All name lookups done by this crate go through an implementation of this trait, so that
+/// applications can route them through their own resolver and apply their caching policies.
+/// See [`Resolver::with_dns_resolver()`](struct.Resolver.html#method.with_dns_resolver).
+pub trait DnsResolve: Send + Sync {
+    /// Return the addresses of the host.
+    fn resolve(&self, host: &str) -> io::Result<Vec<IpAddr>>;
+}
+
+/// The future returned by [`AsyncDnsResolve::resolve()`](trait.AsyncDnsResolve.html#tymethod.resolve).
+pub type DnsFuture<'a> = Pin<Box<dyn Future<Output = io::Result<Vec<IpAddr>>> + Send + 'a>>;
+
+/// An asynchronous DNS resolver.
+///
+/// This is the counterpart of [`DnsResolve`](trait.DnsResolve.html) for the asynchronous parts
+/// of the crate. It's runtime-agnostic: the returned future is driven by whatever executor
+/// polls it.
+pub trait AsyncDnsResolve: Send + Sync {
+    /// Return the addresses of the host.
+    fn resolve<'a>(&'a self, host: &'a str) -> DnsFuture<'a>;
+}
+
+/// The resolver of the operating system, as used by the standard library.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemDns;
+
+impl DnsResolve for SystemDns {
+    fn resolve(&self, host: &str) -> io::Result<Vec<IpAddr>> {
+        Ok((host, 0).to_socket_addrs()?.map(|sa| sa.ip()).collect())
+    }
+}
+
+impl<T: DnsResolve + ?Sized> DnsResolve for Arc<T> {
+    fn resolve(&self, host: &str) -> io::Result<Vec<IpAddr>> {
+        (**self).resolve(host)
+    }
+}
+
+impl<F> DnsResolve for F where F: Fn(&str) -> io::Result<Vec<IpAddr>> + Send + Sync {
+    fn resolve(&self, host: &str) -> io::Result<Vec<IpAddr>> {
+        self(host)
+    }
+}
+
+/// A shareable handle to a DNS resolver, kept by the `Resolver`.
+#[derive(Clone)]
+pub(crate) struct DnsHandle(pub(crate) Arc<dyn DnsResolve>);
+
+impl fmt::Debug for DnsHandle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("DnsHandle")
+    }
+}
@@ -0,0 +1,123 @@
+// Copyright (c) 2016 Ivan Nejgebauer <inejge@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::error;
+use std::fmt;
+
+use url::ParseError;
+
+use crate::redact_password;
+
+/// An error encountered while turning an environment value into a proxy URL.
+///
+/// Errors caused by a proxy value carry the name of the variable which held it. The value
+/// itself is never included, since proxy URLs routinely contain credentials.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Error {
+    /// The value could not be parsed as a URL.
+    Parse { var: String, source: ParseError },
+    /// The host part of the URL is empty.
+    EmptyHost { var: String },
+    /// The URL has no port, and no default port was configured.
+    UnknownPort { var: String },
+    /// The value contains whitespace (strict mode only.)
+    Whitespace { var: String },
+    /// The URL scheme is not one of the supported proxy schemes (strict mode only.)
+    UnsupportedScheme { var: String, scheme: String },
+    /// The URL has a path, query or fragment, which a proxy URL can't use (strict mode only.)
+    ExtraComponent { var: String, component: &'static str },
+    /// The URL scheme is not among the schemes allowed by the resolver.
+    DisallowedScheme { var: String, scheme: String },
+    /// The proxy endpoint is on the resolver's deny-list.
+    DeniedProxy { var: String, endpoint: String },
+    /// The connection to the proxy would not be encrypted, violating the resolver's policy.
+    UnencryptedProxy { var: String, scheme: String },
+    /// The target of the resolution can't be turned into a URL with a host.
+    InvalidTarget { target: String },
+    /// The proxy endpoint is the same as the target's (strict mode only.)
+    ProxyLoop { var: String, endpoint: String },
+    /// The evaluation of a PAC script failed.
+    PacScript { message: String },
+    /// The configuration file can't be read or is invalid.
+    ConfigFile { message: String },
+    /// The value references a variable which is not defined (variable expansion only.)
+    UndefinedVariable { var: String, name: String },
+    /// The variable references in the value can't be expanded (variable expansion only.)
+    Expansion { var: String, problem: &'static str },
+    /// There is no proxy value to convert.
+    NoValue,
+    /// An entry of a configuration string is malformed; `entry` is its position, counted from 1.
+    ConfigString { entry: usize },
+    /// The proxy URL can't be represented as an `http::Uri`.
+    InvalidUri { var: String },
+}
+
+impl Error {
+    /// Return the name of the variable whose value caused the error, if any.
+    pub fn var(&self) -> Option<&str> {
+        match *self {
+            Error::Parse { ref var, .. } |
+            Error::EmptyHost { ref var } |
+            Error::UnknownPort { ref var } |
+            Error::Whitespace { ref var } |
+            Error::UnsupportedScheme { ref var, .. } |
+            Error::ExtraComponent { ref var, .. } |
+            Error::DisallowedScheme { ref var, .. } |
+            Error::DeniedProxy { ref var, .. } |
+            Error::UnencryptedProxy { ref var, .. } |
+            Error::ProxyLoop { ref var, .. } |
+            Error::UndefinedVariable { ref var, .. } |
+            Error::Expansion { ref var, .. } |
+            Error::InvalidUri { ref var } => Some(var),
+            Error::InvalidTarget { .. } | Error::PacScript { .. } | Error::ConfigFile { .. } |
+            Error::ConfigString { .. } | Error::NoValue => None,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Parse { ref var, ref source } => write!(f, "{}: url parse error: {}", var, source),
+            Error::EmptyHost { ref var } => write!(f, "{}: host part of the URL is empty", var),
+            Error::UnknownPort { ref var } => write!(f, "{}: the port of the URL is unknown", var),
+            Error::Whitespace { ref var } => write!(f, "{}: the value contains whitespace", var),
+            Error::UnsupportedScheme { ref var, ref scheme } =>
+                write!(f, "{}: unsupported proxy URL scheme '{}'", var, scheme),
+            Error::ExtraComponent { ref var, component } =>
+                write!(f, "{}: proxy URL must not have a {}", var, component),
+            Error::DisallowedScheme { ref var, ref scheme } =>
+                write!(f, "{}: proxy URL scheme '{}' is not allowed", var, scheme),
+            Error::DeniedProxy { ref var, ref endpoint } =>
+                write!(f, "{}: proxy endpoint {} is denied", var, endpoint),
+            Error::UnencryptedProxy { ref var, ref scheme } =>
+                write!(f, "{}: proxy URL scheme '{}' is not encrypted", var, scheme),
+            Error::InvalidTarget { ref target } => write!(f, "invalid target '{}'", redact_password(target)),
+            Error::ProxyLoop { ref var, ref endpoint } =>
+                write!(f, "{}: proxy endpoint {} is the target itself", var, endpoint),
+            Error::PacScript { ref message } => write!(f, "PAC script error: {}", message),
+            Error::ConfigFile { ref message } => write!(f, "configuration file error: {}", message),
+            Error::UndefinedVariable { ref var, ref name } =>
+                write!(f, "{}: referenced variable {} is not defined", var, name),
+            Error::Expansion { ref var, problem } => write!(f, "{}: can't expand the value: {}", var, problem),
+            Error::NoValue => write!(f, "no proxy value"),
+            Error::ConfigString { entry } =>
+                write!(f, "configuration string: entry {} is not a NAME=value assignment", entry),
+            Error::InvalidUri { ref var } => write!(f, "{}: proxy URL is not a valid URI", var),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            Error::Parse { ref source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,279 @@
+// Copyright (c) 2016 Ivan Nejgebauer <inejge@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::fmt::Write;
+use std::net::{IpAddr, Ipv4Addr};
+
+use url::Url;
+
+use crate::no_proxy::{parse_cidr, LOCAL};
+use crate::ProxyConfig;
+
+/// The maximum length of an environment variable value on Windows.
+pub const ENV_VALUE_LIMIT: usize = 32_767;
+
+/// The maximum length of a command line on Windows.
+pub const COMMAND_LINE_LIMIT: usize = 32_767;
+
+/// A warning about a generated output which may be too large for its consumer.
+///
+/// The limits are those of Windows, which are the lowest among the common platforms.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ExportWarning {
+    /// The value of the variable is longer than [`ENV_VALUE_LIMIT`](constant.ENV_VALUE_LIMIT.html).
+    EnvValueTooLong { var: &'static str, len: usize, limit: usize },
+    /// The command-line switches are longer than [`COMMAND_LINE_LIMIT`](constant.COMMAND_LINE_LIMIT.html).
+    CommandLineTooLong { switches: &'static str, len: usize, limit: usize },
+}
+
+impl std::fmt::Display for ExportWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            ExportWarning::EnvValueTooLong { var, len, limit } =>
+                write!(f, "value of {} is {} characters long, exceeding the limit of {}", var, len, limit),
+            ExportWarning::CommandLineTooLong { switches, len, limit } =>
+                write!(f, "{} switches are {} characters long, exceeding the limit of {}", switches, len, limit),
+        }
+    }
+}
+
+/// Return the PAC directive for a canonical proxy value, or `None` if it can't be used.
+fn pac_directive(value: &str) -> Option<String> {
+    let url = Url::parse(value).ok()?;
+    let host = url.host_str()?;
+    let port = url.port_or_known_default()?;
+    let kind = match url.scheme() {
+        "http" => "PROXY",
+        "https" => "HTTPS",
+        "socks4" | "socks4a" => "SOCKS",
+        "socks5" | "socks5h" => "SOCKS5",
+        _ => return None,
+    };
+    Some(format!("{} {}:{}", kind, host, port))
+}
+
+/// Return the Chromium proxy URI for a canonical proxy value, or `None` if it can't be used.
+fn chromium_proxy_uri(value: &str) -> Option<String> {
+    let url = Url::parse(value).ok()?;
+    let host = url.host_str()?;
+    let port = url.port_or_known_default()?;
+    let scheme = match url.scheme() {
+        "http" => "http",
+        "https" => "https",
+        "socks4" | "socks4a" => "socks4",
+        "socks5" | "socks5h" => "socks5",
+        _ => return None,
+    };
+    Some(format!("{}://{}:{}", scheme, host, port))
+}
+
+pub(crate) fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let bits = chunk.iter().enumerate().fold(0u32, |acc, (i, &b)| acc | u32::from(b) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(bits >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+/// Quote a string as a JavaScript string literal.
+fn js_quote(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+    for ch in s.chars() {
+        match ch {
+            '"' | '\\' => {
+                quoted.push('\\');
+                quoted.push(ch);
+            },
+            ch if (ch as u32) < 0x20 => {
+                let _ = write!(quoted, "\\u{:04x}", ch as u32);
+            },
+            ch => quoted.push(ch),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+impl ProxyConfig {
+    /// Generate a PAC script implementing the same routing as [`for_url()`](fn.for_url.html).
+    ///
+    /// The script's `FindProxyForURL()` checks the host against the __no_proxy__ list, then
+    /// chooses the proxy by the scheme of the URL, with the same precedence of variables as
+    /// this crate. IPv4 networks in the list are checked with `isInNet()` only for hosts which
+    /// are IPv4 addresses; IPv6 networks can't be expressed in PAC and are skipped. The `<local>`
+    /// entry is checked with `isPlainHostName()`. The script can be handed to embedded browsers
+    /// and other PAC consumers, so that they make the same decisions as the application.
+    ///
+    /// PAC can't express proxy credentials, which are omitted. A proxy value which can't be parsed,
+    /// or whose scheme has no PAC equivalent, is translated to `DIRECT`, matching the behavior of
+    /// [`ProxyUrl::to_url()`](struct.ProxyUrl.html#method.to_url) returning `None`.
+    pub fn to_pac_script(&self) -> String {
+        let mut pac = String::from("function FindProxyForURL(url, host) {\n");
+        if self.no_proxy().matches_all() {
+            pac.push_str("    return \"DIRECT\";\n}\n");
+            return pac;
+        }
+        let names = self.no_proxy().entries()
+            .filter(|&entry| parse_cidr(entry).is_none() && entry != LOCAL)
+            .collect::<Vec<_>>();
+        let networks = self.no_proxy().entries()
+            .filter_map(parse_cidr)
+            .filter_map(|(addr, prefix)| match addr {
+                IpAddr::V4(addr) => Some((addr, Ipv4Addr::from(u32::MAX.checked_shl(32 - u32::from(prefix)).unwrap_or(0)))),
+                IpAddr::V6(_) => None,
+            })
+            .collect::<Vec<_>>();
+        if self.no_proxy().entries().any(|entry| entry == LOCAL) {
+            pac.push_str("    if (isPlainHostName(host)) {\n        return \"DIRECT\";\n    }\n");
+        }
+        if !networks.is_empty() {
+            pac.push_str("    if (/^[0-9]+\\.[0-9]+\\.[0-9]+\\.[0-9]+$/.test(host)) {\n");
+            for (addr, mask) in networks {
+                let _ = write!(pac, "        if (isInNet(host, \"{}\", \"{}\")) {{\n            return \"DIRECT\";\n        }}\n", addr, mask);
+            }
+            pac.push_str("    }\n");
+        }
+        if !names.is_empty() {
+            let entries = names.into_iter().map(js_quote).collect::<Vec<_>>().join(", ");
+            let _ = write!(pac, concat!(
+                "    host = host.toLowerCase();\n",
+                "    var bypass = [{}];\n",
+                "    for (var i = 0; i < bypass.length; i++) {{\n",
+                "        if (host == bypass[i] || dnsDomainIs(host, \".\" + bypass[i])) {{\n",
+                "            return \"DIRECT\";\n",
+                "        }}\n",
+                "    }}\n"), entries);
+        }
+        let directive = |value: Option<&str>| value.and_then(pac_directive).unwrap_or_else(|| "DIRECT".to_string());
+        let specific = [("http:", self.http_proxy()), ("https:", self.https_proxy()), ("ftp:", self.ftp_proxy())];
+        for &(prefix, value) in specific.iter() {
+            if value.is_some() {
+                let _ = write!(pac, "    if (url.substring(0, {}) == {}) {{\n        return {};\n    }}\n",
+                    prefix.len(), js_quote(prefix), js_quote(&directive(value)));
+            }
+        }
+        let _ = writeln!(pac, "    return {};\n}}", js_quote(&directive(self.all_proxy())));
+        pac
+    }
+
+    /// Generate the proxy-related command-line switches for Chromium-based browsers.
+    ///
+    /// The switches are `--proxy-server`, with a per-scheme mapping for __http__, __https__ and
+    /// __ftp__ following the same precedence of variables as [`for_url()`](fn.for_url.html),
+    /// and `--proxy-bypass-list`, translated from the __no_proxy__ list. Each name in the list
+    /// is emitted both as-is and with a `*.` prefix, since Chromium matches a plain name only
+    /// against the identical host name; networks and `<local>` are emitted as-is. The special
+    /// `<-loopback>` entry is always added, because Chromium would otherwise bypass the proxy for
+    /// loopback addresses on its own, which this crate doesn't do. If no proxy is configured, or
+    /// __no_proxy__ is '&#8239;__*__&#8239;', the only switch is `--no-proxy-server`.
+    ///
+    /// Chromium can't use proxy credentials from the command line, and they are omitted. A value
+    /// which can't be parsed is translated to a direct connection for its scheme.
+    pub fn to_chromium_switches(&self) -> Vec<String> {
+        let no_proxy_server = vec!["--no-proxy-server".to_string()];
+        if self.no_proxy().matches_all() {
+            return no_proxy_server;
+        }
+        let uri = |value: Option<&str>| value.and_then(chromium_proxy_uri).unwrap_or_else(|| "direct://".to_string());
+        let all = self.all_proxy();
+        let mapping = [("http", self.http_proxy()), ("https", self.https_proxy()), ("ftp", self.ftp_proxy())];
+        let proxy_server = if mapping.iter().all(|&(_, value)| value.is_none()) {
+            match all {
+                Some(all) => uri(Some(all)),
+                None => return no_proxy_server,
+            }
+        } else {
+            mapping.iter()
+                .map(|&(scheme, value)| format!("{}={}", scheme, uri(value.or(all))))
+                .collect::<Vec<_>>()
+                .join(";")
+        };
+        let mut bypass = self.no_proxy().entries()
+            .flat_map(|entry| match parse_cidr(entry) {
+                None if entry != LOCAL => vec![entry.to_string(), format!("*.{}", entry)],
+                _ => vec![entry.to_string()],
+            })
+            .collect::<Vec<_>>();
+        bypass.push("<-loopback>".to_string());
+        vec![format!("--proxy-server={}", proxy_server), format!("--proxy-bypass-list={}", bypass.join(";"))]
+    }
+
+    /// Generate the `--proxy-pac-url` switch for Chromium-based browsers.
+    ///
+    /// The switch points to a `data:` URL containing the script generated by
+    /// [`to_pac_script()`](#method.to_pac_script), so that no file or server is needed.
+    pub fn to_chromium_pac_switch(&self) -> String {
+        format!("--proxy-pac-url=data:application/x-ns-proxy-autoconfig;base64,{}", base64(self.to_pac_script().as_bytes()))
+    }
+
+    /// Return an equivalent configuration with the __no_proxy__ list compacted.
+    ///
+    /// See [`NoProxy::aggregate()`](struct.NoProxy.html#method.aggregate). Generated outputs of
+    /// the compacted configuration make the same decisions, but may be considerably shorter.
+    pub fn compacted(&self) -> ProxyConfig {
+        let mut config = self.clone();
+        config.no_proxy = self.no_proxy.aggregate();
+        config
+    }
+
+    /// Check the sizes of the generated outputs against platform limits.
+    ///
+    /// The checked outputs are the variable values, as they would be exported into the environment,
+    /// and the Chromium command-line switches. If any of them are too long, try checking the
+    /// [compacted](#method.compacted) configuration.
+    pub fn export_warnings(&self) -> Vec<ExportWarning> {
+        let mut warnings = Vec::new();
+        let no_proxy = self.no_proxy().to_string();
+        let values = [
+            ("http_proxy", self.http_proxy()),
+            ("https_proxy", self.https_proxy()),
+            ("ftp_proxy", self.ftp_proxy()),
+            ("all_proxy", self.all_proxy()),
+            ("no_proxy", Some(no_proxy.as_str())),
+        ];
+        for &(var, value) in values.iter() {
+            let len = value.map(|v| v.chars().count()).unwrap_or(0);
+            if len > ENV_VALUE_LIMIT {
+                warnings.push(ExportWarning::EnvValueTooLong { var, len, limit: ENV_VALUE_LIMIT });
+            }
+        }
+        let switches = self.to_chromium_switches();
+        let len = switches.iter().map(|s| s.len() + 1).sum::<usize>();
+        if len > COMMAND_LINE_LIMIT {
+            warnings.push(ExportWarning::CommandLineTooLong { switches: "Chromium proxy", len, limit: COMMAND_LINE_LIMIT });
+        }
+        let len = self.to_chromium_pac_switch().len();
+        if len > COMMAND_LINE_LIMIT {
+            warnings.push(ExportWarning::CommandLineTooLong { switches: "Chromium PAC", len, limit: COMMAND_LINE_LIMIT });
+        }
+        warnings
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::base64;
+
+    #[test]
+    fn base64_padding() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64(b"foobar"), "Zm9vYmFy");
+    }
+}
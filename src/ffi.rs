@@ -0,0 +1,182 @@
+// Copyright (c) 2016 Ivan Nejgebauer <inejge@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! A C interface to proxy resolution (requires the `ffi` feature.)
+//!
+//! The functions in this module let programs in other languages resolve proxies exactly as
+//! [`for_url()`](../fn.for_url.html) does. The `env_proxy-ffi` package of the workspace builds
+//! them into a shared and a static library, `env_proxy_c`, with
+//!
+//! ```text
+//! cargo build --release -p env_proxy-ffi
+//! ```
+//!
+//! and declares them in `include/env_proxy.h`. All strings are NUL-terminated UTF-8.
+//! Strings returned by the library are owned by the caller, and must be released with the
+//! matching function of this module, not with `free()`.
+
+use std::ffi::{c_char, c_int, CStr, CString};
+use std::ptr;
+
+use percent_encoding::percent_decode_str;
+use url::Url;
+
+use crate::default_resolver;
+use crate::trace::warn;
+
+/// The resolution succeeded.
+pub const ENV_PROXY_OK: c_int = 0;
+/// The target is a null pointer, isn't valid UTF-8, or isn't a URL.
+pub const ENV_PROXY_INVALID_TARGET: c_int = 1;
+/// The proxy value for the target is unusable.
+pub const ENV_PROXY_INVALID_PROXY: c_int = 2;
+
+/// The proxy determined for a target, filled in by [`env_proxy_for_url()`](fn.env_proxy_for_url.html).
+///
+/// For a direct connection, all pointers are null and the port is zero. The user name and
+/// password are percent-decoded; either may be null if the proxy URL doesn't have it.
+#[repr(C)]
+#[derive(Debug)]
+pub struct EnvProxyResult {
+    /// The proxy URL scheme, e.g., `http` or `socks5h`.
+    pub scheme: *mut c_char,
+    /// The proxy host, with IPv6 addresses in brackets.
+    pub host: *mut c_char,
+    /// The proxy port.
+    pub port: u16,
+    /// The user name for the proxy.
+    pub user: *mut c_char,
+    /// The password for the proxy.
+    pub password: *mut c_char,
+    /// The name of the variable or provider which supplied the proxy.
+    pub source: *mut c_char,
+    /// Non-zero if the target matched the effective bypass list of the resolver.
+    pub bypass: c_int,
+}
+
+impl EnvProxyResult {
+    fn direct(bypass: bool) -> EnvProxyResult {
+        EnvProxyResult {
+            scheme: ptr::null_mut(),
+            host: ptr::null_mut(),
+            port: 0,
+            user: ptr::null_mut(),
+            password: ptr::null_mut(),
+            source: ptr::null_mut(),
+            bypass: bypass as c_int,
+        }
+    }
+}
+
+/// Copy the string for the caller, or return a null pointer if it contains a NUL.
+fn c_string(s: &str) -> *mut c_char {
+    CString::new(s).map(CString::into_raw).unwrap_or(ptr::null_mut())
+}
+
+/// Read the target URL, logging the reason if it's unusable.
+unsafe fn target(url: *const c_char) -> Option<Url> {
+    if url.is_null() {
+        return None;
+    }
+    let url = CStr::from_ptr(url).to_str().ok()?;
+    Url::parse(url).inspect_err(|e| warn!("invalid target: {}", e)).ok()
+}
+
+/// Determine the proxy for the target URL.
+///
+/// On success, `ENV_PROXY_OK` is returned, and the result is filled in; it must be released
+/// with [`env_proxy_result_free()`](fn.env_proxy_result_free.html). Otherwise, an error code is
+/// returned, and the result is filled in as for a direct connection.
+///
+/// # Safety
+///
+/// `url` must be null or point to a NUL-terminated string, and `result` must point to writable
+/// memory for an `EnvProxyResult`.
+#[no_mangle]
+pub unsafe extern "C" fn env_proxy_for_url(url: *const c_char, result: *mut EnvProxyResult) -> c_int {
+    if result.is_null() {
+        return ENV_PROXY_INVALID_TARGET;
+    }
+    result.write(EnvProxyResult::direct(false));
+    let url = match target(url) {
+        Some(url) => url,
+        None => return ENV_PROXY_INVALID_TARGET,
+    };
+    let resolver = default_resolver();
+    let proxy = resolver.try_for_url(&url).and_then(|proxy| {
+        let source = proxy.source();
+        proxy.try_to_url().map(|url| url.map(|url| (url, source)))
+    });
+    let (proxy, source) = match proxy {
+        Ok(Some(proxy)) => proxy,
+        Ok(None) => {
+            result.write(EnvProxyResult::direct(resolver.is_bypassed(&url)));
+            return ENV_PROXY_OK;
+        },
+        Err(e) => {
+            warn!("{}", e);
+            return ENV_PROXY_INVALID_PROXY;
+        },
+    };
+    let decoded = |s: &str| c_string(&percent_decode_str(s).decode_utf8_lossy());
+    result.write(EnvProxyResult {
+        scheme: c_string(proxy.scheme()),
+        host: proxy.host_str().map(c_string).unwrap_or(ptr::null_mut()),
+        port: proxy.port_or_known_default().unwrap_or(0),
+        user: Some(proxy.username()).filter(|user| !user.is_empty()).map(decoded).unwrap_or(ptr::null_mut()),
+        password: proxy.password().map(decoded).unwrap_or(ptr::null_mut()),
+        source: source.map(c_string).unwrap_or(ptr::null_mut()),
+        bypass: 0,
+    });
+    ENV_PROXY_OK
+}
+
+/// Release the strings of a result, and reset it as for a direct connection.
+///
+/// # Safety
+///
+/// `result` must be null or point to a result filled in by [`env_proxy_for_url()`](fn.env_proxy_for_url.html),
+/// whose strings haven't been released.
+#[no_mangle]
+pub unsafe extern "C" fn env_proxy_result_free(result: *mut EnvProxyResult) {
+    if result.is_null() {
+        return;
+    }
+    let r = &mut *result;
+    for s in [r.scheme, r.host, r.user, r.password, r.source] {
+        env_proxy_string_free(s);
+    }
+    result.write(EnvProxyResult::direct(false));
+}
+
+/// Return the proxy URL for the target URL, with credentials, or a null pointer for a direct
+/// connection or an error.
+///
+/// The returned string must be released with [`env_proxy_string_free()`](fn.env_proxy_string_free.html).
+///
+/// # Safety
+///
+/// `url` must be null or point to a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn env_proxy_url(url: *const c_char) -> *mut c_char {
+    match target(url).and_then(|url| default_resolver().for_url(&url).to_url()) {
+        Some(proxy) => c_string(proxy.as_str()),
+        None => ptr::null_mut(),
+    }
+}
+
+/// Release a string returned by the library.
+///
+/// # Safety
+///
+/// `s` must be null or a string returned by the library, which hasn't been released.
+#[no_mangle]
+pub unsafe extern "C" fn env_proxy_string_free(s: *mut c_char) {
+    if !s.is_null() {
+        drop(CString::from_raw(s));
+    }
+}
@@ -0,0 +1,62 @@
+// Copyright (c) 2016 Ivan Nejgebauer <inejge@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use git2::ProxyOptions;
+use url::Url;
+
+use crate::trace::warn;
+use crate::{default_resolver, ProxyKind, Resolver};
+
+impl Resolver {
+    /// Return the libgit2 proxy options for a remote URL (requires the `git2` feature.)
+    ///
+    /// The proxy is the one determined for the remote by [`for_url()`](#method.for_url), with
+    /// credentials, if any, kept in the proxy URL, where libgit2 finds them. If the remote
+    /// should be connected to directly, the options specify no proxy, rather than libgit2's
+    /// reading of the git configuration and the environment, so that the __no_proxy__ rules of
+    /// this crate apply. libgit2 uses a proxy only for `http` and `https` remotes, and only
+    /// reaches `http` and `https` proxies; other remotes, including scp-like `user@host:path`
+    /// ones, get no proxy, and a proxy with another scheme is logged and not used.
+    pub fn git2_proxy_options(&self, remote_url: &str) -> ProxyOptions<'static> {
+        let mut options = ProxyOptions::new();
+        let remote = match Url::parse(remote_url) {
+            Ok(remote) if remote.scheme() == "http" || remote.scheme() == "https" => remote,
+            _ => return options,
+        };
+        if let Some(proxy) = self.for_url(&remote).to_url() {
+            match ProxyKind::from_scheme(proxy.scheme()) {
+                Some(ProxyKind::Http) | Some(ProxyKind::Https) => {
+                    options.url(proxy.as_str());
+                },
+                _ => warn!("proxy URL scheme '{}' is not supported by libgit2", proxy.scheme()),
+            }
+        }
+        options
+    }
+}
+
+/// Return the libgit2 proxy options for a remote URL (requires the `git2` feature.)
+///
+/// The proxy is determined with the resolver of [`for_url()`](fn.for_url.html), as described
+/// for [`Resolver::git2_proxy_options()`](struct.Resolver.html#method.git2_proxy_options).
+///
+/// ```no_run
+/// # extern crate env_proxy;
+/// # extern crate git2;
+/// # fn main() -> Result<(), git2::Error> {
+/// let remote_url = "https://github.com/inejge/env_proxy.git";
+/// let mut fetch_options = git2::FetchOptions::new();
+/// fetch_options.proxy_options(env_proxy::git2_proxy_options(remote_url));
+/// let repo = git2::build::RepoBuilder::new()
+///     .fetch_options(fetch_options)
+///     .clone(remote_url, std::path::Path::new("env_proxy"))?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn git2_proxy_options(remote_url: &str) -> ProxyOptions<'static> {
+    default_resolver().git2_proxy_options(remote_url)
+}
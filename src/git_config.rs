@@ -0,0 +1,281 @@
+// Copyright (c) 2016 Ivan Nejgebauer <inejge@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use url::Url;
+
+use crate::{parse_proxy_value, ProviderResult, ProxyProvider};
+
+/// The port curl, and therefore git, assumes for a proxy value without one.
+const GIT_DEFAULT_PROXY_PORT: u16 = 1080;
+
+/// A URL pattern of an `http.<url>.*` section.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct UrlPattern {
+    scheme: String,
+    user: Option<String>,
+    host: String,
+    port: Option<u16>,
+    path: String,
+}
+
+/// The quality of a match, compared in field order; a better match is greater.
+type MatchQuality = (bool, usize, bool);
+
+impl UrlPattern {
+    fn parse(pattern: &str) -> Option<UrlPattern> {
+        // Wildcards aren't valid in host names, so replace them before parsing.
+        let url = Url::parse(&pattern.replace('*', "wildcard-label-")).ok()?;
+        let path = url.path().trim_end_matches('/').to_string();
+        Some(UrlPattern {
+            scheme: url.scheme().to_string(),
+            user: Some(url.username().to_string()).filter(|user| !user.is_empty()),
+            host: url.host_str()?.replace("wildcard-label-", "*"),
+            port: url.port_or_known_default(),
+            path,
+        })
+    }
+
+    /// Return the quality of the match with the URL, if it matches.
+    fn matches(&self, url: &Url) -> Option<MatchQuality> {
+        if url.scheme() != self.scheme || url.port_or_known_default() != self.port {
+            return None;
+        }
+        if let Some(ref user) = self.user {
+            if url.username() != user {
+                return None;
+            }
+        }
+        let host = url.host_str()?;
+        let exact = host.eq_ignore_ascii_case(&self.host);
+        if !exact {
+            let labels = host.split('.').collect::<Vec<_>>();
+            let pattern = self.host.split('.').collect::<Vec<_>>();
+            if labels.len() != pattern.len()
+                || !labels.iter().zip(&pattern).all(|(label, p)| *p == "*" || label.eq_ignore_ascii_case(p))
+            {
+                return None;
+            }
+        }
+        let path = url.path();
+        let prefix = self.path.is_empty() || path == self.path || path.starts_with(&format!("{}/", self.path));
+        if !prefix {
+            return None;
+        }
+        Some((exact, self.path.len(), self.user.is_some()))
+    }
+}
+
+/// Remove comments, quotes and escapes from a value.
+fn config_value(raw: &str) -> String {
+    let mut value = String::new();
+    let mut quoted = false;
+    let mut chars = raw.trim().chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => quoted = !quoted,
+            '#' | ';' if !quoted => break,
+            '\\' => match chars.next() {
+                Some('n') => value.push('\n'),
+                Some('t') => value.push('\t'),
+                Some('b') => { value.pop(); },
+                Some(c) => value.push(c),
+                None => (),
+            },
+            c => value.push(c),
+        }
+    }
+    value.trim_end().to_string()
+}
+
+/// Parse the `http.proxy` and `http.<url>.proxy` entries of a git configuration file.
+fn parse_git_config(contents: &str, entries: &mut Vec<(Option<UrlPattern>, String)>) {
+    let contents = contents.replace("\\\r\n", "").replace("\\\n", "");
+    let mut section: Option<Option<UrlPattern>> = None;
+    for line in contents.lines().map(str::trim) {
+        if line.starts_with('#') || line.starts_with(';') || line.is_empty() {
+            continue;
+        }
+        let mut line = line;
+        if let Some(header) = line.strip_prefix('[') {
+            let end = match header.find(']') {
+                Some(end) => end,
+                None => continue,
+            };
+            let (name, subsection) = match header[..end].split_once(char::is_whitespace) {
+                Some((name, subsection)) => (name, Some(config_value(subsection))),
+                None => match header[..end].split_once('.') {
+                    Some((name, subsection)) => (name, Some(subsection.to_ascii_lowercase())),
+                    None => (&header[..end], None),
+                },
+            };
+            section = if !name.eq_ignore_ascii_case("http") {
+                None
+            } else {
+                match subsection {
+                    Some(url) => UrlPattern::parse(&url).map(Some),
+                    None => Some(None),
+                }
+            };
+            line = header[end + 1..].trim();
+            if line.is_empty() {
+                continue;
+            }
+        }
+        let pattern = match section {
+            Some(ref pattern) => pattern,
+            None => continue,
+        };
+        let (key, value) = line.split_once('=').unwrap_or((line, ""));
+        if key.trim().eq_ignore_ascii_case("proxy") {
+            entries.push((pattern.clone(), config_value(value)));
+        }
+    }
+}
+
+/// The configuration files of git, in the order git reads them.
+fn config_paths(repo: Option<&Path>) -> Vec<PathBuf> {
+    let var = |name| env::var_os(name).filter(|v| !v.is_empty()).map(PathBuf::from);
+    let mut paths = vec![var("GIT_CONFIG_SYSTEM").unwrap_or_else(|| PathBuf::from("/etc/gitconfig"))];
+    match var("GIT_CONFIG_GLOBAL") {
+        Some(path) => paths.push(path),
+        None => {
+            let home = var("HOME");
+            if let Some(dir) = var("XDG_CONFIG_HOME").or_else(|| home.as_ref().map(|home| home.join(".config"))) {
+                paths.push(dir.join("git").join("config"));
+            }
+            paths.extend(home.map(|home| home.join(".gitconfig")));
+        },
+    }
+    if let Some(repo) = repo {
+        let dot_git = repo.join(".git");
+        paths.push(if dot_git.is_dir() { dot_git.join("config") } else { repo.join("config") });
+    }
+    paths
+}
+
+/// The proxy settings of git (`http.proxy` and `http.<url>.proxy`).
+///
+/// The settings are matched against the target URL with git's rules: the most specific
+/// `http.<url>.proxy` entry applies, and `http.proxy` applies if none does. A pattern matches
+/// if its scheme, host and port equal the target's (the host may contain `*` in place of a
+/// whole label), its path is a prefix of the target's at a `/` boundary, and its user name, if
+/// any, equals the target's. A pattern with an exact host name is more specific than one with
+/// a wildcard, then a longer path is more specific, then a pattern with a user name; among
+/// equally specific entries, the last one read wins. An empty value means no proxy.
+///
+/// The proxy values are interpreted as curl, which git uses, interprets them: the default scheme
+/// is `http` and the default port is 1080. The provider reports its proxies under the name `git`.
+/// `include` and `includeIf` directives are not followed.
+#[derive(Clone, Debug, Default)]
+pub struct GitProxyConfig {
+    entries: Vec<(Option<UrlPattern>, String)>,
+}
+
+impl GitProxyConfig {
+    /// Read the system, global and, if `repo` is given, the repository's git configuration.
+    ///
+    /// __GIT_CONFIG_SYSTEM__ and __GIT_CONFIG_GLOBAL__ are honored as by git. `repo` is either
+    /// a working tree containing `.git`, or a bare repository. Missing files are skipped.
+    pub fn load(repo: Option<&Path>) -> io::Result<GitProxyConfig> {
+        let mut contents = Vec::new();
+        for path in config_paths(repo) {
+            match fs::read_to_string(path) {
+                Ok(file) => contents.push(file),
+                Err(ref e) if e.kind() == io::ErrorKind::NotFound => (),
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(GitProxyConfig::parse(contents.iter().map(String::as_str)))
+    }
+
+    /// Parse the contents of git configuration files, in the order git would read them.
+    pub fn parse<'a, I: IntoIterator<Item = &'a str>>(files: I) -> GitProxyConfig {
+        let mut entries = Vec::new();
+        for contents in files {
+            parse_git_config(contents, &mut entries);
+        }
+        GitProxyConfig { entries }
+    }
+
+    /// Return the proxy value git would use for the URL, if any.
+    ///
+    /// The value is returned as written in the configuration; an empty value means no proxy.
+    pub fn proxy_for(&self, url: &Url) -> Option<&str> {
+        let mut best: Option<(MatchQuality, &str)> = None;
+        let mut default = None;
+        for (pattern, value) in &self.entries {
+            match *pattern {
+                None => default = Some(value.as_str()),
+                Some(ref pattern) => if let Some(quality) = pattern.matches(url) {
+                    if best.map(|(best, _)| quality >= best).unwrap_or(true) {
+                        best = Some((quality, value));
+                    }
+                },
+            }
+        }
+        best.map(|(_, value)| value).or(default)
+    }
+}
+
+impl ProxyProvider for GitProxyConfig {
+    fn proxies_for(&self, url: &Url) -> ProviderResult {
+        match self.proxy_for(url) {
+            None => ProviderResult::Pass,
+            Some("") => ProviderResult::Direct,
+            Some(value) => {
+                let value = match parse_proxy_value("git", value, "http", Some(GIT_DEFAULT_PROXY_PORT), false) {
+                    Ok(url) => url.into(),
+                    Err(_) => value.to_string(),
+                };
+                ProviderResult::Proxies(vec![value])
+            },
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        "git"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use url::Url;
+
+    use super::GitProxyConfig;
+    use crate::{ProviderResult, ProxyProvider};
+
+    #[test]
+    fn url_matching() {
+        let global = "[http]\n\tproxy = proxy.example.com ; default\n\
+            [http \"https://*.example.com\"]\n\tproxy = wildcard.example.com:3128\n\
+            [http \"https://git.example.com\"]\n\tproxy = \"exact.example.com:3128\"\n\
+            [http \"https://git.example.com/team\"]\n\tProxy = team.example.com:3128\n\
+            [http \"https://alice@git.example.com/team\"]\n\tproxy = alice.example.com:3128\n\
+            [core]\n\tproxy = unrelated\n";
+        let local = "[http \"https://git.example.com/team/direct.git\"]\n\tproxy = \"\"\n";
+        let config = GitProxyConfig::parse(vec![global, local]);
+        let proxy = |url: &str| config.proxy_for(&Url::parse(url).unwrap()).map(str::to_string);
+        assert_eq!(proxy("http://git.example.com/"), Some("proxy.example.com".to_string()));
+        assert_eq!(proxy("https://code.example.com/repo.git"), Some("wildcard.example.com:3128".to_string()));
+        assert_eq!(proxy("https://git.example.com/other/repo.git"), Some("exact.example.com:3128".to_string()));
+        assert_eq!(proxy("https://git.example.com/team/repo.git"), Some("team.example.com:3128".to_string()));
+        assert_eq!(proxy("https://git.example.com/teams/repo.git"), Some("exact.example.com:3128".to_string()));
+        assert_eq!(proxy("https://alice@git.example.com/team/repo.git"), Some("alice.example.com:3128".to_string()));
+        assert_eq!(proxy("https://git.example.com:8443/team/repo.git"), Some("proxy.example.com".to_string()));
+        assert_eq!(proxy("https://a.b.example.com/"), Some("proxy.example.com".to_string()));
+        let url = Url::parse("http://git.example.com/").unwrap();
+        assert_eq!(config.proxies_for(&url), ProviderResult::Proxies(vec!["http://proxy.example.com:1080/".to_string()]));
+        let url = Url::parse("https://git.example.com/team/direct.git").unwrap();
+        assert_eq!(config.proxies_for(&url), ProviderResult::Direct);
+        assert_eq!(GitProxyConfig::parse(vec!["[http.https://git.example.com]\nproxy=p:1"]).proxy_for(&url), Some("p:1"));
+    }
+}
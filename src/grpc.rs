@@ -0,0 +1,217 @@
+// Copyright (c) 2016 Ivan Nejgebauer <inejge@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Proxy routing for gRPC channels (requires the `grpc` feature.)
+//!
+//! gRPC runs over HTTP/2, which an ordinary forwarding HTTP proxy can't relay. A channel which
+//! must go through an HTTP proxy therefore always needs a `CONNECT` tunnel to the target, whether
+//! the target URI uses `https` or plaintext `http` (h2c). Only the choice of the proxy variable
+//! depends on the target scheme: __https_proxy__ for `https`, __http_proxy__ for `http`, with the
+//! usual fallback to __all_proxy__.
+//!
+//! The returned [`GrpcRoute`](enum.GrpcRoute.html) tells the channel setup code what to do; with
+//! __tonic__, a `Direct` route means that `Endpoint::connect()` can be used as-is, while the other
+//! routes need a connector which opens the tunnel, passed to `Endpoint::connect_with_connector()`.
+//!
+//! With the `tonic` feature, [`connector()`](fn.connector.html) returns such a connector, which
+//! handles every route, and [`connect()`](fn.connect.html) uses it to connect a channel:
+//!
+//! ```no_run
+//! # extern crate env_proxy;
+//! # extern crate tonic;
+//! # #[cfg(feature = "tonic")]
+//! # async fn channel() -> Result<tonic::transport::Channel, tonic::transport::Error> {
+//! let endpoint = tonic::transport::Endpoint::from_static("http://grpc.example.com:50051");
+//! env_proxy::grpc::connect(&endpoint).await
+//! # }
+//! # fn main() {}
+//! ```
+
+use std::fmt;
+
+use http::Uri;
+use url::Url;
+
+#[cfg(feature = "tonic")]
+use crate::connector::ProxyConnector;
+use crate::{redact_password, Error, Resolver};
+
+/// The way to reach a gRPC target.
+///
+/// The `Debug` output has the proxy password replaced by asterisks.
+#[derive(Clone, PartialEq, Eq)]
+pub enum GrpcRoute {
+    /// Connect to the target directly.
+    Direct,
+    /// Connect to the HTTP(S) proxy and issue `CONNECT authority` to open a tunnel.
+    Connect { proxy: Url, authority: String },
+    /// Connect through the SOCKS proxy to `authority`.
+    Socks { proxy: Url, authority: String },
+}
+
+impl fmt::Debug for GrpcRoute {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            GrpcRoute::Direct => f.write_str("Direct"),
+            GrpcRoute::Connect { ref proxy, ref authority } => f.debug_struct("Connect")
+                .field("proxy", &redact_password(proxy.as_str()))
+                .field("authority", authority)
+                .finish(),
+            GrpcRoute::Socks { ref proxy, ref authority } => f.debug_struct("Socks")
+                .field("proxy", &redact_password(proxy.as_str()))
+                .field("authority", authority)
+                .finish(),
+        }
+    }
+}
+
+impl GrpcRoute {
+    /// Return the proxy URL, or `None` for a direct connection.
+    pub fn proxy(&self) -> Option<&Url> {
+        match *self {
+            GrpcRoute::Direct => None,
+            GrpcRoute::Connect { ref proxy, .. } | GrpcRoute::Socks { ref proxy, .. } => Some(proxy),
+        }
+    }
+}
+
+/// Determine the route for a gRPC target URI, using a resolver with the default settings.
+///
+/// See [`Resolver::grpc_route()`](../struct.Resolver.html#method.grpc_route).
+pub fn route_for(uri: &Uri) -> Result<GrpcRoute, Error> {
+    Resolver::new().grpc_route(uri)
+}
+
+impl Resolver {
+    /// Determine the route for a gRPC target URI.
+    ///
+    /// A URI without a scheme is treated as `http`, and a URI without a port uses the default
+    /// port for its scheme. The URI must have a host. An error is also returned if the proxy
+    /// value can't be parsed, or if its scheme is neither HTTP(S) nor SOCKS.
+    pub fn grpc_route(&self, uri: &Uri) -> Result<GrpcRoute, Error> {
+        let invalid = || Error::InvalidTarget { target: uri.to_string() };
+        let host = uri.host().ok_or_else(invalid)?;
+        let scheme = uri.scheme_str().unwrap_or("http");
+        let port = uri.port_u16().unwrap_or(if scheme == "https" { 443 } else { 80 });
+        let authority = format!("{}:{}", host, port);
+        let target = Url::parse(&format!("{}://{}/", scheme, authority)).map_err(|_| invalid())?;
+        let proxy = self.try_for_url(&target)?;
+        let var = proxy.var;
+        let proxy = match proxy.try_to_url()? {
+            Some(proxy) => proxy,
+            None => return Ok(GrpcRoute::Direct),
+        };
+        match proxy.scheme() {
+            "http" | "https" => Ok(GrpcRoute::Connect { proxy, authority }),
+            s if s.starts_with("socks") => Ok(GrpcRoute::Socks { proxy, authority }),
+            s => Err(Error::UnsupportedScheme { var: var.to_string(), scheme: s.to_string() }),
+        }
+    }
+}
+
+/// Return a connector for tonic channels, using a resolver with the default settings (requires
+/// the `tonic` feature.)
+///
+/// See [`Resolver::grpc_connector()`](../struct.Resolver.html#method.grpc_connector).
+#[cfg(feature = "tonic")]
+pub fn connector() -> ProxyConnector<hyper_util::client::legacy::connect::HttpConnector> {
+    Resolver::new().grpc_connector()
+}
+
+/// Connect a tonic channel to the endpoint, through a proxy if one is needed (requires the
+/// `tonic` feature.)
+///
+/// The connection is made with [`connector()`](fn.connector.html). TLS to the target, if the
+/// endpoint is configured for it, is set up by tonic over the tunnel.
+#[cfg(feature = "tonic")]
+pub async fn connect(endpoint: &tonic::transport::Endpoint) -> Result<tonic::transport::Channel, tonic::transport::Error> {
+    endpoint.connect_with_connector(connector()).await
+}
+
+#[cfg(feature = "tonic")]
+impl Resolver {
+    /// Return a connector for tonic channels (requires the `tonic` feature.)
+    ///
+    /// The connector follows the [route](#method.grpc_route) for each target: it dials a direct
+    /// route itself, opens a `CONNECT` tunnel through an HTTP(S) proxy, plaintext targets
+    /// included, and performs the SOCKS handshake for the target's authority with a SOCKS proxy.
+    /// The connector is passed to `Endpoint::connect_with_connector()` or
+    /// `Endpoint::connect_with_connector_lazy()`.
+    pub fn grpc_connector(&self) -> ProxyConnector<hyper_util::client::legacy::connect::HttpConnector> {
+        let mut inner = hyper_util::client::legacy::connect::HttpConnector::new();
+        inner.enforce_http(false);
+        ProxyConnector::with_resolver(inner, self.clone()).with_tunnel_all(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn invalid_target() {
+        let uri: Uri = "/just/a/path".parse().unwrap();
+        assert_eq!(route_for(&uri), Err(Error::InvalidTarget { target: "/just/a/path".to_string() }));
+    }
+
+    #[test]
+    fn routes() {
+        let route = |vars: Vec<(&str, &str)>, uri: &str| Resolver::new().with_vars(vars).grpc_route(&uri.parse().unwrap());
+        let url = |s| Url::parse(s).unwrap();
+        assert_eq!(route(vec![], "http://grpc.example.com:50051"), Ok(GrpcRoute::Direct));
+        assert_eq!(
+            route(vec![("http_proxy", "proxy.example.com:3128")], "grpc.example.com:50051"),
+            Ok(GrpcRoute::Connect { proxy: url("http://proxy.example.com:3128"), authority: "grpc.example.com:50051".to_string() })
+        );
+        assert_eq!(
+            route(vec![("https_proxy", "https://secure.example.com:3129"), ("http_proxy", "proxy.example.com:3128")], "https://grpc.example.com"),
+            Ok(GrpcRoute::Connect { proxy: url("https://secure.example.com:3129"), authority: "grpc.example.com:443".to_string() })
+        );
+        let route_socks = route(vec![("all_proxy", "socks5h://localhost:9050")], "https://grpc.example.com:8443");
+        assert_eq!(
+            route_socks,
+            Ok(GrpcRoute::Socks { proxy: url("socks5h://localhost:9050"), authority: "grpc.example.com:8443".to_string() })
+        );
+        assert_eq!(route_socks.unwrap().proxy(), Some(&url("socks5h://localhost:9050")));
+        assert_eq!(
+            route(vec![("all_proxy", "proxy.example.com:3128"), ("no_proxy", "example.org")], "https://grpc.example.org"),
+            Ok(GrpcRoute::Direct)
+        );
+    }
+
+    #[cfg(feature = "tonic")]
+    #[test]
+    fn socks_connector() {
+        use std::io::{Read, Write};
+        use std::net::TcpListener;
+
+        use tower_service::Service;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let proxy = format!("socks5h://{}", listener.local_addr().unwrap());
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut greeting = [0u8; 3];
+            stream.read_exact(&mut greeting).unwrap();
+            stream.write_all(b"\x05\x00").unwrap();
+            let mut request = [0u8; 23];
+            stream.read_exact(&mut request).unwrap();
+            stream.write_all(b"\x05\x00\x00\x01\x7f\x00\x00\x01\xc3\x83").unwrap();
+            (greeting, request)
+        });
+        let resolver = Resolver::new().with_vars(vec![("all_proxy", proxy.as_str())]);
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+        runtime.block_on(async {
+            let mut connector = resolver.grpc_connector();
+            let stream = connector.call("http://grpc.example.com:50051".parse().unwrap()).await.unwrap();
+            assert!(!stream.is_forwarding());
+        });
+        let (greeting, request) = server.join().unwrap();
+        assert_eq!(&greeting, b"\x05\x01\x00");
+        assert_eq!(&request[..], &b"\x05\x01\x00\x03\x10grpc.example.com\xc3\x83"[..]);
+    }
+}
@@ -0,0 +1,142 @@
+// Copyright (c) 2016 Ivan Nejgebauer <inejge@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::collections::VecDeque;
+use std::time::SystemTime;
+
+use url::Url;
+
+use crate::config::{fnv1a, FNV_OFFSET};
+use crate::{Error, Selection};
+
+/// The outcome of a single resolution.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Outcome {
+    /// The proxy from the named variable was chosen.
+    Proxy { var: &'static str },
+    /// The proxy from the named variable was chosen among several candidates by the strategy,
+    /// or in place of candidates demoted after a failure; `index` is its position among the
+    /// candidates in order of precedence, counted from 0.
+    Selected { var: &'static str, strategy: Selection, index: usize },
+    /// The target matched __no_proxy__.
+    Bypassed,
+    /// The named provider answered that the target should be connected to directly.
+    Direct { provider: &'static str },
+    /// No proxy variable applicable to the target was defined.
+    NotConfigured,
+    /// The proxy from the named variable pointed to the target itself, and was skipped.
+    Loop { var: &'static str },
+    /// The resolution failed with the error.
+    Failed(Error),
+}
+
+/// A resolution recorded in the resolver's history.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Decision {
+    time: SystemTime,
+    target: u64,
+    outcome: Outcome,
+}
+
+impl Decision {
+    /// Return the time of the resolution.
+    pub fn time(&self) -> SystemTime {
+        self.time
+    }
+
+    /// Return the hash of the target's origin (scheme, host and port.)
+    ///
+    /// The target itself is not kept, since URLs may contain credentials or other sensitive
+    /// data. The hash is computed with the same algorithm as
+    /// [`ProxyConfig::fingerprint()`](struct.ProxyConfig.html#method.fingerprint), so it can be
+    /// matched against a known target with [`target_hash()`](fn.target_hash.html).
+    pub fn target(&self) -> u64 {
+        self.target
+    }
+
+    /// Return the outcome of the resolution.
+    pub fn outcome(&self) -> &Outcome {
+        &self.outcome
+    }
+}
+
+/// Counts of the outcomes in the resolver's history.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Stats {
+    /// The number of resolutions which chose a proxy.
+    pub proxied: usize,
+    /// The number of resolutions which chose a direct connection, for any reason.
+    pub direct: usize,
+    /// The number of failed resolutions.
+    pub failed: usize,
+}
+
+/// Return the hash of the URL's origin, as recorded in a [`Decision`](struct.Decision.html).
+pub fn target_hash(url: &Url) -> u64 {
+    fnv1a(FNV_OFFSET, url.origin().ascii_serialization().bytes())
+}
+
+/// A bounded buffer of recent decisions.
+#[derive(Debug)]
+pub(crate) struct History {
+    capacity: usize,
+    decisions: VecDeque<Decision>,
+}
+
+impl History {
+    pub(crate) fn new(capacity: usize) -> History {
+        History { capacity, decisions: VecDeque::with_capacity(capacity) }
+    }
+
+    pub(crate) fn record(&mut self, target: u64, outcome: Outcome) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.decisions.len() == self.capacity {
+            self.decisions.pop_front();
+        }
+        self.decisions.push_back(Decision { time: SystemTime::now(), target, outcome });
+    }
+
+    pub(crate) fn decisions(&self) -> Vec<Decision> {
+        self.decisions.iter().cloned().collect()
+    }
+
+    pub(crate) fn stats(&self) -> Stats {
+        let mut stats = Stats::default();
+        for decision in &self.decisions {
+            match decision.outcome {
+                Outcome::Proxy { .. } | Outcome::Selected { .. } => stats.proxied += 1,
+                Outcome::Failed(_) => stats.failed += 1,
+                _ => stats.direct += 1,
+            }
+        }
+        stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use url::Url;
+
+    use super::{target_hash, History, Outcome};
+
+    #[test]
+    fn bounded() {
+        let mut history = History::new(2);
+        let url = Url::parse("http://www.example.org").unwrap();
+        history.record(target_hash(&url), Outcome::Bypassed);
+        history.record(target_hash(&url), Outcome::NotConfigured);
+        history.record(target_hash(&url), Outcome::Proxy { var: "http_proxy" });
+        let decisions = history.decisions();
+        assert_eq!(decisions.len(), 2);
+        assert_eq!(decisions[0].outcome(), &Outcome::NotConfigured);
+        assert_eq!(history.stats().proxied, 1);
+        assert_eq!(history.stats().direct, 1);
+    }
+}
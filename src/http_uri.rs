@@ -0,0 +1,155 @@
+// Copyright (c) 2016 Ivan Nejgebauer <inejge@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use http::header::HOST;
+use http::uri::{Authority, Scheme};
+use http::{Request, Uri};
+use url::Url;
+
+use crate::trace::warn;
+use crate::{default_resolver, Error, ProxyUrl, Resolver};
+
+/// Convert an absolute URI into the URL used for resolution.
+///
+/// The URI must have a scheme and a host; the URL has the same scheme, host, port, path and
+/// query, so that the result of a resolution is the same as for the URL.
+pub(crate) fn uri_target(uri: &Uri) -> Result<Url, Error> {
+    let invalid = || Error::InvalidTarget { target: uri.to_string() };
+    if uri.scheme().is_none() || uri.host().is_none() {
+        return Err(invalid());
+    }
+    Url::parse(&uri.to_string()).map_err(|_| invalid())
+}
+
+/// Determine the URL of a request's target, used for resolution.
+///
+/// The scheme is taken from the URI, or from a `Scheme` in the request extensions, such as one
+/// inserted by the server which accepted the connection; without either, it's `http`. The host
+/// and port are taken from the URI, or from the `Host` header. The path and query are the URI's.
+pub(crate) fn request_target<B>(request: &Request<B>) -> Result<Url, Error> {
+    let uri = request.uri();
+    if uri.scheme().is_some() && uri.host().is_some() {
+        return uri_target(uri);
+    }
+    let invalid = || Error::InvalidTarget { target: uri.to_string() };
+    let scheme = uri.scheme().or_else(|| request.extensions().get::<Scheme>()).map(Scheme::as_str).unwrap_or("http");
+    let authority = match uri.authority() {
+        Some(authority) if uri.host().is_some() => authority.clone(),
+        _ => request.headers().get(HOST)
+            .and_then(|host| host.to_str().ok())
+            .and_then(|host| host.parse::<Authority>().ok())
+            .ok_or_else(invalid)?,
+    };
+    let path = uri.path_and_query().map(|path| path.as_str()).filter(|path| path.starts_with('/')).unwrap_or("/");
+    Url::parse(&format!("{}://{}{}", scheme, authority, path)).map_err(|_| invalid())
+}
+
+impl ProxyUrl {
+    /// Transform the raw proxy URL into an `http::Uri` (requires the `http` feature.)
+    ///
+    /// The raw URL will first be transformed into a `Url`, with any errors in the conversion
+    /// producing a `None` (see [`to_url()`](#method.to_url)). Credentials, if any, are kept
+    /// in the authority of the URI.
+    pub fn to_uri(self) -> Option<Uri> {
+        self.try_to_uri()
+            .inspect_err(|e| warn!("{}", e))
+            .unwrap_or(None)
+    }
+
+    /// Transform the raw proxy URL into an `http::Uri`, reporting the reason for failure
+    /// (requires the `http` feature.)
+    ///
+    /// See [`to_uri()`](#method.to_uri) and [`try_to_url()`](#method.try_to_url). A wrapped
+    /// `None` value produces `Ok(None)`.
+    pub fn try_to_uri(self) -> Result<Option<Uri>, Error> {
+        let var = self.var;
+        match self.try_to_url()? {
+            Some(url) => url.as_str().parse().map(Some).map_err(|_| Error::InvalidUri { var: var.to_string() }),
+            None => Ok(None),
+        }
+    }
+}
+
+impl Resolver {
+    /// Determine proxy parameters for an `http::Uri` (requires the `http` feature.)
+    ///
+    /// The URI must be absolute, with a scheme and a host; it's resolved as the URL with the
+    /// same components would be by [`for_url()`](#method.for_url). If it's not absolute, a
+    /// warning is logged and a `ProxyUrl` wrapping `None` is returned.
+    pub fn for_uri(&self, uri: &Uri) -> ProxyUrl {
+        match uri_target(uri) {
+            Ok(url) => self.for_url(&url),
+            Err(e) => {
+                warn!("{}", e);
+                ProxyUrl::none()
+            },
+        }
+    }
+
+    /// Determine proxy parameters for an `http::Uri`, reporting an unusable proxy value or
+    /// target as an error (requires the `http` feature.)
+    ///
+    /// See [`for_uri()`](#method.for_uri) and [`try_for_url()`](#method.try_for_url). A URI
+    /// which is not absolute produces `Error::InvalidTarget`.
+    pub fn try_for_uri(&self, uri: &Uri) -> Result<ProxyUrl, Error> {
+        self.try_for_url(&uri_target(uri)?)
+    }
+
+    /// Determine proxy parameters for the target of an `http::Request` (requires the `http`
+    /// feature.)
+    ///
+    /// The target is the request URI if it's absolute. Otherwise, as for a request received by
+    /// a server or gateway, the scheme is taken from a `http::uri::Scheme` in the request
+    /// extensions, defaulting to `http`, and the host and port from the `Host` header. The
+    /// target is then resolved as by [`for_url()`](#method.for_url). If the target can't be
+    /// determined, a warning is logged and a `ProxyUrl` wrapping `None` is returned.
+    pub fn for_request<B>(&self, request: &Request<B>) -> ProxyUrl {
+        match request_target(request) {
+            Ok(url) => self.for_url(&url),
+            Err(e) => {
+                warn!("{}", e);
+                ProxyUrl::none()
+            },
+        }
+    }
+
+    /// Determine proxy parameters for the target of an `http::Request`, reporting an unusable
+    /// proxy value or target as an error (requires the `http` feature.)
+    ///
+    /// See [`for_request()`](#method.for_request) and [`try_for_url()`](#method.try_for_url). A
+    /// request whose target can't be determined produces `Error::InvalidTarget`.
+    pub fn try_for_request<B>(&self, request: &Request<B>) -> Result<ProxyUrl, Error> {
+        self.try_for_url(&request_target(request)?)
+    }
+}
+
+/// Determine proxy parameters for an `http::Uri` (requires the `http` feature.)
+///
+/// The proxy is determined with the resolver of [`for_url()`](fn.for_url.html), as described
+/// for [`Resolver::for_uri()`](struct.Resolver.html#method.for_uri).
+///
+/// ```
+/// # extern crate env_proxy;
+/// # extern crate http;
+/// # fn main() {
+/// let uri: http::Uri = "http://www.example.org/".parse().unwrap();
+/// if let Some(proxy) = env_proxy::for_uri(&uri).to_uri() {
+///     println!("Proxy: {}", proxy);
+/// }
+/// # }
+/// ```
+pub fn for_uri(uri: &Uri) -> ProxyUrl {
+    default_resolver().for_uri(uri)
+}
+
+/// Determine proxy parameters for the target of an `http::Request` (requires the `http` feature.)
+///
+/// The proxy is determined with the resolver of [`for_url()`](fn.for_url.html), as described
+/// for [`Resolver::for_request()`](struct.Resolver.html#method.for_request).
+pub fn for_request<B>(request: &Request<B>) -> ProxyUrl {
+    default_resolver().for_request(request)
+}
@@ -0,0 +1,69 @@
+// Copyright (c) 2016 Ivan Nejgebauer <inejge@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use isahc::auth::Credentials;
+use isahc::config::Configurable;
+use percent_encoding::percent_decode_str;
+use url::Url;
+
+use crate::trace::warn;
+use crate::{default_resolver, Resolver};
+
+impl Resolver {
+    /// Configure an isahc client or request builder for the target URL (requires the `isahc`
+    /// feature.)
+    ///
+    /// The proxy is the one determined for the target by [`for_url()`](#method.for_url); its
+    /// credentials are set separately, decoded, and the proxy URI is set without them. If the
+    /// target should be connected to directly, the proxy is disabled, instead of being left to
+    /// libcurl's reading of the environment. The bypass list is the resolver's
+    /// [effective __no_proxy__ list](#method.effective_no_proxy), translated as libcurl
+    /// understands it, so that requests for other targets made with the same client are routed
+    /// similarly; the decision for the given target is always this crate's. Configuring each
+    /// request, rather than the client, applies this crate's rules to every request. A proxy value
+    /// which can't be used is logged, and the proxy is disabled.
+    pub fn apply_to_isahc<C: Configurable>(&self, builder: C, target: &Url) -> C {
+        let mut builder = builder.proxy_blacklist(self.effective_no_proxy().curl_entries());
+        let mut proxy = match self.for_url(target).to_url() {
+            Some(proxy) => proxy,
+            None => return builder.proxy(None),
+        };
+        if !proxy.username().is_empty() {
+            let user = percent_decode_str(proxy.username()).decode_utf8_lossy().into_owned();
+            let password = proxy.password().map(|p| percent_decode_str(p).decode_utf8_lossy().into_owned()).unwrap_or_default();
+            builder = builder.proxy_credentials(Credentials::new(user, password));
+            proxy.set_username("").unwrap_or(());
+            proxy.set_password(None).unwrap_or(());
+        }
+        match proxy.as_str().parse::<isahc::http::Uri>() {
+            Ok(uri) => builder.proxy(uri),
+            Err(e) => {
+                warn!("proxy can't be used with isahc: {}", e);
+                builder.proxy(None)
+            },
+        }
+    }
+}
+
+/// Configure an isahc client or request builder for the target URL (requires the `isahc`
+/// feature.)
+///
+/// The proxy is determined with the resolver of [`for_url()`](fn.for_url.html), as described
+/// for [`Resolver::apply_to_isahc()`](struct.Resolver.html#method.apply_to_isahc).
+///
+/// ```no_run
+/// # extern crate env_proxy;
+/// # extern crate isahc;
+/// # extern crate url;
+/// # fn main() {
+/// let url = url::Url::parse("https://www.example.com/").unwrap();
+/// let client = env_proxy::apply_to_isahc(isahc::HttpClient::builder(), &url).build();
+/// # }
+/// ```
+pub fn apply_to_isahc<C: Configurable>(builder: C, target: &Url) -> C {
+    default_resolver().apply_to_isahc(builder, target)
+}
@@ -0,0 +1,175 @@
+// Copyright (c) 2016 Ivan Nejgebauer <inejge@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::collections::BTreeMap;
+use std::env;
+
+use url::Url;
+
+use crate::{matches_no_proxy, parse_proxy_value, NoProxy, ProviderResult, ProxyProvider};
+
+/// The variables holding JVM options, in the order the JVM applies them.
+const JAVA_OPTIONS_VARS: &[&str] = &["JAVA_TOOL_OPTIONS", "JDK_JAVA_OPTIONS"];
+
+/// The value of `http.nonProxyHosts` if it's not set.
+const DEFAULT_NON_PROXY_HOSTS: &str = "localhost|127.*|[::1]";
+
+/// Split a JVM options string into options, honoring single and double quotes.
+fn split_options(options: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut quote = None;
+    for c in options.chars() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => word.push(c),
+            None if c == '"' || c == '\'' => {
+                quote = Some(c);
+                in_word = true;
+            },
+            None if c.is_whitespace() => if in_word {
+                words.push(std::mem::take(&mut word));
+                in_word = false;
+            },
+            None => {
+                word.push(c);
+                in_word = true;
+            },
+        }
+    }
+    if in_word {
+        words.push(word);
+    }
+    words
+}
+
+/// A proxy of a protocol, with the hosts it doesn't apply to.
+#[derive(Clone, Debug)]
+struct JavaProxy {
+    value: String,
+    non_proxy_hosts: NoProxy,
+}
+
+/// The proxy system properties of the JVM, set with `-D` options.
+///
+/// The properties are `http.proxyHost` and `http.proxyPort` (80 by default), `https.proxyHost`
+/// and `https.proxyPort` (443 by default), `ftp.proxyHost` and `ftp.proxyPort` (80 by default),
+/// `socksProxyHost`, `socksProxyPort` (1080 by default) and `socksProxyVersion` (5 by default),
+/// and `http.nonProxyHosts` and `ftp.nonProxyHosts`, lists of host names separated by `|`, which
+/// may contain wildcards and are translated as described for
+/// [`NoProxy::from_windows_override()`](struct.NoProxy.html#method.from_windows_override). As in
+/// the JVM, `http.nonProxyHosts` applies to both HTTP and HTTPS, and is `localhost|127.*|[::1]`
+/// if not set.
+///
+/// As a provider, it gives the proxy of the target's scheme, or the direct connection if the
+/// host matches the non-proxy hosts; if the scheme has no proxy, the SOCKS proxy, if any, is used
+/// for any target. Names are resolved locally with a SOCKS proxy, as in the JVM, so its scheme
+/// is `socks5` or `socks4`. The provider reports its proxies under the name `java`.
+#[derive(Clone, Debug, Default)]
+pub struct JavaProxyConfig {
+    http: Option<JavaProxy>,
+    https: Option<JavaProxy>,
+    ftp: Option<JavaProxy>,
+    socks: Option<String>,
+}
+
+impl JavaProxyConfig {
+    /// Read the properties from __JAVA_TOOL_OPTIONS__ and __JDK_JAVA_OPTIONS__.
+    ///
+    /// If a property is set in both, the value from __JDK_JAVA_OPTIONS__ is used, since the JVM
+    /// applies it later. Values which are not valid UTF-8 are ignored.
+    pub fn from_env() -> JavaProxyConfig {
+        let options = JAVA_OPTIONS_VARS.iter().filter_map(|var| env::var(var).ok()).collect::<Vec<_>>();
+        JavaProxyConfig::parse(options.iter().map(String::as_str))
+    }
+
+    /// Parse JVM options strings, in the order the JVM applies them.
+    ///
+    /// Options other than `-D` options of the proxy properties are ignored.
+    pub fn parse<'a, I: IntoIterator<Item = &'a str>>(options: I) -> JavaProxyConfig {
+        let mut props = BTreeMap::new();
+        for option in options.into_iter().flat_map(split_options) {
+            if let Some((key, value)) = option.strip_prefix("-D").and_then(|prop| prop.split_once('=')) {
+                props.insert(key.to_string(), value.trim().to_string());
+            }
+        }
+        let prop = |key: &str| props.get(key).map(String::as_str).filter(|value| !value.is_empty());
+        let non_proxy_hosts = |key: &str, default: Option<&str>| {
+            prop(key).or(default).map(|hosts| NoProxy::from_windows_override(&hosts.replace('|', ";"))).unwrap_or_default()
+        };
+        let proxy = |scheme: &str, prefix: &str, default_port: u16, non_proxy_hosts: NoProxy| {
+            let host = prop(&format!("{}Host", prefix))?;
+            let port = prop(&format!("{}Port", prefix)).and_then(|port| port.parse::<u16>().ok()).unwrap_or(default_port);
+            let value = parse_proxy_value("java", &format!("{}:{}", host, port), scheme, None, false).ok()?;
+            Some(JavaProxy { value: value.into(), non_proxy_hosts })
+        };
+        let http_non_proxy_hosts = non_proxy_hosts("http.nonProxyHosts", Some(DEFAULT_NON_PROXY_HOSTS));
+        let socks_scheme = if prop("socksProxyVersion") == Some("4") { "socks4" } else { "socks5" };
+        JavaProxyConfig {
+            http: proxy("http", "http.proxy", 80, http_non_proxy_hosts.clone()),
+            https: proxy("http", "https.proxy", 443, http_non_proxy_hosts),
+            ftp: proxy("http", "ftp.proxy", 80, non_proxy_hosts("ftp.nonProxyHosts", None)),
+            socks: proxy(socks_scheme, "socksProxy", 1080, NoProxy::default()).map(|proxy| proxy.value),
+        }
+    }
+
+    /// Return `true` if no proxy is set.
+    pub fn is_empty(&self) -> bool {
+        self.http.is_none() && self.https.is_none() && self.ftp.is_none() && self.socks.is_none()
+    }
+}
+
+impl ProxyProvider for JavaProxyConfig {
+    fn proxies_for(&self, url: &Url) -> ProviderResult {
+        let specific = match url.scheme() {
+            "https" => &self.https,
+            "http" => &self.http,
+            "ftp" => &self.ftp,
+            _ => &None,
+        };
+        match (specific, &self.socks) {
+            (Some(proxy), _) if matches_no_proxy(url, &proxy.non_proxy_hosts, None) => ProviderResult::Direct,
+            (Some(proxy), _) => ProviderResult::Proxies(vec![proxy.value.clone()]),
+            (None, Some(socks)) => ProviderResult::Proxies(vec![socks.clone()]),
+            (None, None) => ProviderResult::Pass,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        "java"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use url::Url;
+
+    use super::JavaProxyConfig;
+    use crate::{ProviderResult, ProxyProvider};
+
+    #[test]
+    fn java_options() {
+        let tool_options = "-Xmx2g -Dhttp.proxyHost=proxy.example.com -Dhttp.proxyPort=3128 \
+            -Dhttps.proxyHost=old.example.com";
+        let jdk_options = "-Dhttps.proxyHost=secure.example.com \"-Dhttp.nonProxyHosts=*.example.org|10.*\" \
+            -DsocksProxyHost=socks.example.com";
+        let config = JavaProxyConfig::parse(vec![tool_options, jdk_options]);
+        let proxy = |url: &str| config.proxies_for(&Url::parse(url).unwrap());
+        let proxies = |value: &str| ProviderResult::Proxies(vec![value.to_string()]);
+        assert_eq!(proxy("http://www.example.com/"), proxies("http://proxy.example.com:3128/"));
+        assert_eq!(proxy("https://www.example.com/"), proxies("http://secure.example.com:443/"));
+        assert_eq!(proxy("https://www.example.org/"), ProviderResult::Direct);
+        assert_eq!(proxy("http://10.1.2.3/"), ProviderResult::Direct);
+        assert_eq!(proxy("ftp://ftp.example.com/"), proxies("socks5://socks.example.com:1080"));
+        let config = JavaProxyConfig::parse(vec!["-Dhttp.proxyHost=proxy.example.com"]);
+        assert_eq!(config.proxies_for(&Url::parse("http://127.0.0.1:8080/").unwrap()), ProviderResult::Direct);
+        assert_eq!(config.proxies_for(&Url::parse("http://[::1]/").unwrap()), ProviderResult::Direct);
+        assert_eq!(config.proxies_for(&Url::parse("https://www.example.com/").unwrap()), ProviderResult::Pass);
+        assert!(JavaProxyConfig::parse(vec!["-Xss1m"]).is_empty());
+    }
+}
@@ -0,0 +1,151 @@
+// Copyright (c) 2016 Ivan Nejgebauer <inejge@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::fmt;
+
+/// The port used by the default resolver when a proxy value doesn't specify one.
+///
+/// This is __8080__, which corresponds to __http-alt__ in the IANA port registry, and is
+/// different from __curl__'s default of 1080.
+pub const DEFAULT_PROXY_PORT: u16 = 8080;
+
+/// The scheme assumed by the default resolver when a proxy value doesn't specify one.
+pub const DEFAULT_PROXY_SCHEME: &str = "http";
+
+/// The kind of a proxy, as determined by the scheme of its URL.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[non_exhaustive]
+pub enum ProxyKind {
+    /// An HTTP proxy, reached over plain TCP (`http`.)
+    Http,
+    /// An HTTP proxy, reached over TLS (`https`.)
+    Https,
+    /// A SOCKS4 proxy, with names resolved locally (`socks4`.)
+    Socks4,
+    /// A SOCKS4a proxy, with names resolved by the proxy (`socks4a`.)
+    Socks4a,
+    /// A SOCKS5 proxy, with names resolved locally (`socks5`.)
+    Socks5,
+    /// A SOCKS5 proxy, with names resolved by the proxy (`socks5h`.)
+    Socks5h,
+}
+
+/// The way a client should reach a target through a proxy, as reported by
+/// [`ProxyKind::dial_mode()`](enum.ProxyKind.html#method.dial_mode).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum DialMode {
+    /// Open a `CONNECT` tunnel to the target's host and port, and speak the target's protocol,
+    /// including its TLS, over it.
+    Tunnel,
+    /// Send the requests to the proxy with the absolute target URL as the request target, and
+    /// let the proxy make the connection.
+    Forward,
+    /// Ask the proxy to connect to the target with the SOCKS handshake for the proxy's scheme,
+    /// and speak the target's protocol over the connection.
+    Socks,
+}
+
+/// All proxy kinds, in the order of declaration.
+pub(crate) const ALL_KINDS: &[ProxyKind] = &[
+    ProxyKind::Http,
+    ProxyKind::Https,
+    ProxyKind::Socks4,
+    ProxyKind::Socks4a,
+    ProxyKind::Socks5,
+    ProxyKind::Socks5h,
+];
+
+impl ProxyKind {
+    /// Determine the kind from the URL scheme, compared case-insensitively.
+    ///
+    /// Returns `None` if the scheme is not one of the supported proxy schemes.
+    pub fn from_scheme(scheme: &str) -> Option<ProxyKind> {
+        ALL_KINDS.iter().cloned().find(|kind| kind.scheme().eq_ignore_ascii_case(scheme))
+    }
+
+    /// Return the URL scheme of the kind.
+    pub fn scheme(self) -> &'static str {
+        match self {
+            ProxyKind::Http => "http",
+            ProxyKind::Https => "https",
+            ProxyKind::Socks4 => "socks4",
+            ProxyKind::Socks4a => "socks4a",
+            ProxyKind::Socks5 => "socks5",
+            ProxyKind::Socks5h => "socks5h",
+        }
+    }
+
+    /// Return `true` if the proxy itself resolves the target host names.
+    pub fn resolves_remotely(self) -> bool {
+        !matches!(self, ProxyKind::Socks4 | ProxyKind::Socks5)
+    }
+
+    /// Return the way to reach a target with the URL scheme through a proxy of this kind.
+    ///
+    /// A SOCKS proxy is always asked to connect. An HTTP or HTTPS proxy forwards the `http`
+    /// and `ftp` requests sent to it in absolute form, which lets it see and cache them, and is
+    /// asked for a tunnel for every other scheme: TLS to the target must run end to end, and
+    /// other protocols, WebSockets included, can't be relayed request by request. A client
+    /// which only handles tunnels may tunnel everything, but forwarding an `https` request is
+    /// never right. The scheme is compared case-insensitively.
+    pub fn dial_mode(self, target_scheme: &str) -> DialMode {
+        match self {
+            ProxyKind::Http | ProxyKind::Https
+                if target_scheme.eq_ignore_ascii_case("http") || target_scheme.eq_ignore_ascii_case("ftp") => DialMode::Forward,
+            ProxyKind::Http | ProxyKind::Https => DialMode::Tunnel,
+            ProxyKind::Socks4 | ProxyKind::Socks4a | ProxyKind::Socks5 | ProxyKind::Socks5h => DialMode::Socks,
+        }
+    }
+}
+
+impl fmt::Display for ProxyKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.scheme())
+    }
+}
+
+/// Return the port which the default resolver applies to a proxy value of the given kind
+/// which doesn't specify one.
+///
+/// The resolver doesn't vary the default by kind: a SOCKS proxy without a port also gets
+/// [`DEFAULT_PROXY_PORT`](constant.DEFAULT_PROXY_PORT.html), unless the default is changed with
+/// [`Resolver::with_default_port()`](struct.Resolver.html#method.with_default_port). Connectors
+/// which display or validate the effective port should use this function instead of hardcoding
+/// the number, so that they stay in agreement with the resolver.
+pub fn default_port(kind: ProxyKind) -> u16 {
+    match kind {
+        ProxyKind::Http | ProxyKind::Https | ProxyKind::Socks4 | ProxyKind::Socks4a
+            | ProxyKind::Socks5 | ProxyKind::Socks5h => DEFAULT_PROXY_PORT,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scheme_round_trip() {
+        for &kind in ALL_KINDS {
+            assert_eq!(ProxyKind::from_scheme(kind.scheme()), Some(kind));
+            assert_eq!(default_port(kind), DEFAULT_PROXY_PORT);
+        }
+        assert_eq!(ProxyKind::from_scheme("SOCKS5H"), Some(ProxyKind::Socks5h));
+        assert_eq!(ProxyKind::from_scheme("ftp"), None);
+    }
+
+    #[test]
+    fn dial_modes() {
+        assert_eq!(ProxyKind::Http.dial_mode("http"), DialMode::Forward);
+        assert_eq!(ProxyKind::Https.dial_mode("FTP"), DialMode::Forward);
+        assert_eq!(ProxyKind::Http.dial_mode("https"), DialMode::Tunnel);
+        assert_eq!(ProxyKind::Https.dial_mode("ws"), DialMode::Tunnel);
+        assert_eq!(ProxyKind::Http.dial_mode("smtp"), DialMode::Tunnel);
+        assert_eq!(ProxyKind::Socks4.dial_mode("http"), DialMode::Socks);
+        assert_eq!(ProxyKind::Socks5h.dial_mode("https"), DialMode::Socks);
+    }
+}
@@ -0,0 +1,214 @@
+// Copyright (c) 2016 Ivan Nejgebauer <inejge@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::{redact_password, NoProxy, ProxyConfig};
+
+/// A named configuration layer, in increasing order of precedence.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Layer {
+    /// The environment variables.
+    Env,
+    /// The settings of the operating system or the desktop environment.
+    System,
+    /// An organization-wide policy.
+    Policy,
+    /// The settings of a tenant in a multi-tenant application.
+    Tenant,
+    /// Overrides for a single request.
+    Request,
+}
+
+impl Layer {
+    /// Return the name of the layer.
+    pub fn name(self) -> &'static str {
+        match self {
+            Layer::Env => "env",
+            Layer::System => "system",
+            Layer::Policy => "policy",
+            Layer::Tenant => "tenant",
+            Layer::Request => "request",
+        }
+    }
+}
+
+impl fmt::Display for Layer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// A field of the proxy configuration.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Field {
+    /// The value of __http_proxy__.
+    HttpProxy,
+    /// The value of __https_proxy__.
+    HttpsProxy,
+    /// The value of __ftp_proxy__.
+    FtpProxy,
+    /// The value of __all_proxy__.
+    AllProxy,
+    /// The __no_proxy__ list.
+    NoProxy,
+}
+
+/// All fields, in the order of declaration.
+const FIELDS: &[Field] = &[Field::HttpProxy, Field::HttpsProxy, Field::FtpProxy, Field::AllProxy, Field::NoProxy];
+
+impl Field {
+    /// Return the name of the variable corresponding to the field.
+    pub fn var(self) -> &'static str {
+        match self {
+            Field::HttpProxy => "http_proxy",
+            Field::HttpsProxy => "https_proxy",
+            Field::FtpProxy => "ftp_proxy",
+            Field::AllProxy => "all_proxy",
+            Field::NoProxy => "no_proxy",
+        }
+    }
+}
+
+impl fmt::Display for Field {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.var())
+    }
+}
+
+/// A proxy configuration assembled from named layers.
+///
+/// Each layer may set any of the fields, with a value or explicitly without one; a field set in
+/// a layer overrides the same field in all layers of lower precedence (see [`Layer`](enum.Layer.html)),
+/// and fields not set in a layer are inherited from below. The effective configuration can be
+/// turned into a [`ProxyConfig`](struct.ProxyConfig.html) and used by a resolver (see
+/// [`Resolver::with_layers()`](struct.Resolver.html#method.with_layers)), and for each field it's
+/// possible to find out which layer determined its value.
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct LayeredConfig {
+    layers: BTreeMap<Layer, BTreeMap<Field, Option<String>>>,
+}
+
+impl fmt::Debug for LayeredConfig {
+    /// Format the layers for debugging, with passwords replaced by asterisks.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut map = f.debug_map();
+        for (layer, fields) in &self.layers {
+            let fields = fields.iter()
+                .map(|(field, value)| (field.var(), value.as_ref().map(|v| redact_password(v).into_owned())))
+                .collect::<BTreeMap<_, _>>();
+            map.entry(layer, &fields);
+        }
+        map.finish()
+    }
+}
+
+impl LayeredConfig {
+    /// Create a configuration without any layers.
+    pub fn new() -> LayeredConfig {
+        LayeredConfig::default()
+    }
+
+    /// Create a configuration with the `Env` layer holding the proxy variables which are defined
+    /// in the environment.
+    pub fn from_env() -> LayeredConfig {
+        let mut config = LayeredConfig::new();
+        let vars = [
+            (Field::HttpProxy, env_var_pair!("http_proxy", "")),
+            (Field::HttpsProxy, env_var_pair!("https_proxy", "HTTPS_PROXY")),
+            (Field::FtpProxy, env_var_pair!("ftp_proxy", "FTP_PROXY")),
+            (Field::AllProxy, env_var_pair!("all_proxy", "ALL_PROXY")),
+            (Field::NoProxy, env_var_pair!("no_proxy", "NO_PROXY")),
+        ];
+        for (field, value) in vars {
+            if let Some((_, value)) = value {
+                config.set(Layer::Env, field, Some(&value));
+            }
+        }
+        config
+    }
+
+    /// Set the field in the layer. A `None` value overrides the lower layers with an unset field.
+    pub fn set(&mut self, layer: Layer, field: Field, value: Option<&str>) -> &mut Self {
+        self.layers.entry(layer).or_default().insert(field, value.map(str::to_string));
+        self
+    }
+
+    /// Remove the field from the layer, so that it's inherited from the lower layers.
+    pub fn clear(&mut self, layer: Layer, field: Field) -> &mut Self {
+        if let Some(fields) = self.layers.get_mut(&layer) {
+            fields.remove(&field);
+            if fields.is_empty() {
+                self.layers.remove(&layer);
+            }
+        }
+        self
+    }
+
+    /// Remove the layer entirely.
+    pub fn clear_layer(&mut self, layer: Layer) -> &mut Self {
+        self.layers.remove(&layer);
+        self
+    }
+
+    /// Return an iterator over the layers which set any fields, in increasing order of precedence.
+    pub fn layers(&self) -> impl Iterator<Item = Layer> + '_ {
+        self.layers.keys().cloned()
+    }
+
+    /// Return the layer which determined the field, or `None` if no layer sets it.
+    pub fn source(&self, field: Field) -> Option<Layer> {
+        self.layers.iter().rev().find(|(_, fields)| fields.contains_key(&field)).map(|(&layer, _)| layer)
+    }
+
+    /// Return the effective raw value of the field.
+    pub fn value(&self, field: Field) -> Option<&str> {
+        self.layers.values().rev().find_map(|fields| fields.get(&field)).and_then(|value| value.as_deref())
+    }
+
+    /// Return the effective configuration.
+    pub fn to_config(&self) -> ProxyConfig {
+        let mut config = ProxyConfig::default();
+        for &field in FIELDS {
+            let value = self.value(field);
+            match field {
+                Field::HttpProxy => config.set_http_proxy(value),
+                Field::HttpsProxy => config.set_https_proxy(value),
+                Field::FtpProxy => config.set_ftp_proxy(value),
+                Field::AllProxy => config.set_all_proxy(value),
+                Field::NoProxy => config.set_no_proxy(value.map(NoProxy::parse).unwrap_or_default()),
+            }
+        }
+        config
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn precedence() {
+        let mut config = LayeredConfig::new();
+        config.set(Layer::Env, Field::HttpProxy, Some("env.example.com:3128"))
+            .set(Layer::Env, Field::NoProxy, Some("example.org"))
+            .set(Layer::Tenant, Field::HttpProxy, Some("tenant.example.com:3128"))
+            .set(Layer::Policy, Field::HttpProxy, Some("policy.example.com:3128"))
+            .set(Layer::Request, Field::NoProxy, None);
+        assert_eq!(config.source(Field::HttpProxy), Some(Layer::Tenant));
+        assert_eq!(config.value(Field::HttpProxy), Some("tenant.example.com:3128"));
+        assert_eq!(config.source(Field::NoProxy), Some(Layer::Request));
+        assert_eq!(config.value(Field::NoProxy), None);
+        assert_eq!(config.source(Field::AllProxy), None);
+        assert_eq!(config.layers().collect::<Vec<_>>(), vec![Layer::Env, Layer::Policy, Layer::Tenant, Layer::Request]);
+        config.clear(Layer::Tenant, Field::HttpProxy);
+        assert_eq!(config.to_config().http_proxy(), Some("http://policy.example.com:3128/"));
+        assert!(config.to_config().no_proxy().is_empty());
+        assert_eq!(config.layers().count(), 3);
+    }
+}
@@ -0,0 +1,247 @@
+// Copyright (c) 2016 Ivan Nejgebauer <inejge@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::ffi::OsString;
+use std::fmt;
+
+use crate::{parse_proxy_value, redact_password, split_proxy_list, NoProxy, DEFAULT_PROXY_PORT, DEFAULT_PROXY_SCHEME};
+
+/// Proxy variables, in lowercase/uppercase pairs.
+const PROXY_VARS: &[(&str, &str)] = &[
+    ("http_proxy", "HTTP_PROXY"),
+    ("https_proxy", "HTTPS_PROXY"),
+    ("ftp_proxy", "FTP_PROXY"),
+    ("all_proxy", "ALL_PROXY"),
+    ("no_proxy", "NO_PROXY"),
+];
+
+/// A machine-applicable change to a single variable.
+#[derive(Clone, PartialEq, Eq)]
+pub enum Edit {
+    /// Set the variable to the value.
+    Set { var: String, value: String },
+    /// Remove the variable.
+    Unset { var: String },
+}
+
+impl Edit {
+    /// Return the name of the variable changed by the edit.
+    pub fn var(&self) -> &str {
+        match *self {
+            Edit::Set { ref var, .. } | Edit::Unset { ref var } => var,
+        }
+    }
+}
+
+impl fmt::Debug for Edit {
+    /// Format the edit for debugging, with passwords replaced by asterisks.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Edit::Set { ref var, ref value } =>
+                f.debug_struct("Set").field("var", var).field("value", &redact_password(value)).finish(),
+            Edit::Unset { ref var } => f.debug_struct("Unset").field("var", var).finish(),
+        }
+    }
+}
+
+/// A problem found in the proxy configuration, with an optional fix.
+///
+/// The message names the variable, but never includes its value. The fix, if present, is a list
+/// of edits which resolve the problem when applied in order; a suggested value may contain the
+/// credentials from the original one.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Lint {
+    var: String,
+    message: String,
+    fix: Vec<Edit>,
+}
+
+impl Lint {
+    fn new(var: &str, message: &str, fix: Vec<Edit>) -> Lint {
+        Lint { var: var.to_string(), message: message.to_string(), fix }
+    }
+
+    /// Return the name of the variable with the problem.
+    pub fn var(&self) -> &str {
+        &self.var
+    }
+
+    /// Return the description of the problem.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// Return the edits which fix the problem; the slice is empty if there's no automatic fix.
+    pub fn fix(&self) -> &[Edit] {
+        &self.fix
+    }
+}
+
+impl fmt::Display for Lint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.var, self.message)
+    }
+}
+
+fn set(var: &str, value: String) -> Edit {
+    Edit::Set { var: var.to_string(), value }
+}
+
+fn unset(var: &str) -> Edit {
+    Edit::Unset { var: var.to_string() }
+}
+
+/// Check the value of a proxy variable, suggesting a cleaned-up value.
+fn lint_proxy_value(var: &str, value: &str, lints: &mut Vec<Lint>) {
+    if value.is_empty() {
+        lints.push(Lint::new(var, "the value is empty", vec![unset(var)]));
+        return;
+    }
+    if let Some(entries) = split_proxy_list(value, false) {
+        for (n, entry) in entries.into_iter().enumerate() {
+            if let Err(e) = parse_proxy_value(var, entry, DEFAULT_PROXY_SCHEME, Some(DEFAULT_PROXY_PORT), true) {
+                let message = e.to_string();
+                let message = message.strip_prefix(&format!("{}: ", var)).unwrap_or(&message);
+                lints.push(Lint::new(var, &format!("entry {} of the list: {}", n + 1, message), Vec::new()));
+            }
+        }
+        return;
+    }
+    let mut fixed: String = value.chars().filter(|c| !c.is_whitespace()).collect();
+    let mut problems = Vec::new();
+    if fixed.len() != value.len() {
+        problems.push("whitespace");
+    }
+    let authority_start = fixed.find("://").map(|pos| pos + 3).unwrap_or(0);
+    if let Some(pos) = fixed[authority_start..].find(['/', '?', '#']) {
+        fixed.truncate(authority_start + pos);
+        problems.push("a trailing path, query or fragment");
+    }
+    if let Some(pos) = fixed.find("://") {
+        if fixed[..pos].bytes().any(|b| b.is_ascii_uppercase()) {
+            let scheme = fixed[..pos].to_ascii_lowercase();
+            fixed.replace_range(..pos, &scheme);
+            problems.push("an uppercase scheme");
+        }
+    }
+    let parsed = parse_proxy_value(var, &fixed, DEFAULT_PROXY_SCHEME, Some(DEFAULT_PROXY_PORT), true);
+    if let Err(e) = parsed {
+        let message = e.to_string();
+        let message = message.strip_prefix(&format!("{}: ", var)).unwrap_or(&message);
+        lints.push(Lint::new(var, message, Vec::new()));
+        return;
+    }
+    if !problems.is_empty() {
+        let message = format!("the value has {}", problems.join("; "));
+        lints.push(Lint::new(var, &message, vec![set(var, fixed)]));
+    }
+}
+
+/// Check the value of a __no_proxy__ variable.
+fn lint_no_proxy(var: &str, value: &str, lints: &mut Vec<Lint>) {
+    let list = NoProxy::parse(value);
+    let aggregated = list.aggregate();
+    if aggregated != list {
+        lints.push(Lint::new(var, "the list has redundant entries", vec![set(var, aggregated.to_string())]));
+    } else if value.split([',', ' ']).any(|elem| elem.is_empty() || elem == ".") && value != "*" {
+        lints.push(Lint::new(var, "the list has empty entries", vec![set(var, list.to_string())]));
+    }
+}
+
+/// Check the proxy variables for problems, suggesting fixes where possible.
+///
+/// The variables are given as name/value pairs; names are matched case-sensitively, and only
+/// all-lowercase and all-uppercase spellings of the standard proxy variables are checked.
+/// Apart from problems with the individual values, the check finds:
+///
+/// * __HTTP_PROXY__, which is ignored for security reasons (see [`for_url()`](fn.for_url.html)),
+///   with a fix renaming it to __http_proxy__ if that isn't defined;
+/// * uppercase variables shadowed by lowercase ones with a different value, with a fix removing
+///   the uppercase variable.
+///
+/// A value which can't be used is reported without a fix. Values which are not valid UTF-8 are
+/// reported as such.
+pub fn lint_vars<I, K, V>(vars: I) -> Vec<Lint>
+    where I: IntoIterator<Item = (K, V)>, K: Into<OsString>, V: Into<OsString>
+{
+    let vars: Vec<(OsString, OsString)> = vars.into_iter().map(|(k, v)| (k.into(), v.into())).collect();
+    let get = |name: &str| vars.iter().rev().find(|(k, _)| k == name).map(|(_, v)| v);
+    let mut lints = Vec::new();
+    for &(lc_var, uc_var) in PROXY_VARS {
+        let lc_value = get(lc_var);
+        let uc_value = get(uc_var);
+        let mut checked = Vec::new();
+        for (var, value) in [(lc_var, lc_value), (uc_var, uc_value)] {
+            let value = match value {
+                Some(value) => value,
+                None => continue,
+            };
+            match value.to_str() {
+                Some(value) => checked.push((var, value)),
+                None => lints.push(Lint::new(var, "the value is not valid UTF-8", Vec::new())),
+            }
+        }
+        if uc_var == "HTTP_PROXY" && uc_value.is_some() {
+            let fix = match (lc_value, uc_value.and_then(|v| v.to_str())) {
+                (None, Some(value)) => vec![set(lc_var, value.to_string()), unset(uc_var)],
+                _ => vec![unset(uc_var)],
+            };
+            lints.push(Lint::new(uc_var, "the variable is ignored; use http_proxy", fix));
+        } else if let (Some(lc), Some(uc)) = (lc_value, uc_value) {
+            if lc != uc {
+                lints.push(Lint::new(uc_var, &format!("the variable is shadowed by {}", lc_var), vec![unset(uc_var)]));
+            }
+        }
+        for (var, value) in checked {
+            if lc_var == "no_proxy" {
+                lint_no_proxy(var, value, &mut lints);
+            } else {
+                lint_proxy_value(var, value, &mut lints);
+            }
+        }
+    }
+    lints
+}
+
+/// Check the proxy variables in the environment for problems, suggesting fixes where possible.
+///
+/// See [`lint_vars()`](fn.lint_vars.html).
+pub fn lint_env() -> Vec<Lint> {
+    lint_vars(std::env::vars_os())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn proxy_value_fixes() {
+        let lints = lint_vars(vec![("https_proxy", " HTTP://user:pw@proxy.example.com:3128/ ")]);
+        assert_eq!(lints.len(), 1);
+        assert_eq!(lints[0].to_string(), "https_proxy: the value has whitespace; a trailing path, query or fragment; an uppercase scheme");
+        assert_eq!(lints[0].fix(), &[set("https_proxy", "http://user:pw@proxy.example.com:3128".to_string())]);
+        assert!(!format!("{:?}", lints).contains("pw"));
+        let lints = lint_vars(vec![("all_proxy", "ftp://proxy.example.com")]);
+        assert_eq!(lints[0].message(), "unsupported proxy URL scheme 'ftp'");
+        assert!(lints[0].fix().is_empty());
+        assert!(lint_vars(vec![("http_proxy", "proxy.example.com:3128")]).is_empty());
+        assert!(lint_vars(vec![("http_proxy", "p1.example.com:3128, p2.example.com:3128")]).is_empty());
+        let lints = lint_vars(vec![("http_proxy", "p1.example.com:3128,ftp://p2.example.com")]);
+        assert_eq!(lints[0].message(), "entry 2 of the list: unsupported proxy URL scheme 'ftp'");
+    }
+
+    #[test]
+    fn variable_fixes() {
+        let lints = lint_vars(vec![("HTTP_PROXY", "proxy.example.com:3128")]);
+        assert_eq!(lints[0].fix(), &[set("http_proxy", "proxy.example.com:3128".to_string()), unset("HTTP_PROXY")]);
+        let lints = lint_vars(vec![("all_proxy", "a.example.com:1"), ("ALL_PROXY", "b.example.com:1")]);
+        assert_eq!(lints[0].var(), "ALL_PROXY");
+        assert_eq!(lints[0].fix(), &[unset("ALL_PROXY")]);
+        let lints = lint_vars(vec![("no_proxy", "example.org,www.example.org,,localhost")]);
+        assert_eq!(lints[0].fix(), &[set("no_proxy", "example.org,localhost".to_string())]);
+    }
+}
@@ -0,0 +1,96 @@
+// Copyright (c) 2016 Ivan Nejgebauer <inejge@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::io;
+use std::net::SocketAddr;
+
+use lettre::transport::smtp::client::{AsyncSmtpConnection, AsyncTokioStream};
+use lettre::transport::smtp::extension::ClientId;
+use url::Url;
+
+use crate::{default_resolver, AsyncProxyStream, Resolver};
+
+impl AsyncTokioStream for AsyncProxyStream {
+    fn peer_addr(&self) -> io::Result<SocketAddr> {
+        self.tcp_stream().peer_addr()
+    }
+}
+
+/// Return the URL used for resolving the proxy of a mail relay.
+fn relay_url(host: &str, port: u16, implicit_tls: bool) -> io::Result<Url> {
+    let scheme = if implicit_tls { "smtps" } else { "smtp" };
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    let url = if host.contains(':') {
+        format!("{}://[{}]:{}", scheme, host, port)
+    } else {
+        format!("{}://{}:{}", scheme, host, port)
+    };
+    Url::parse(&url).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, format!("invalid mail relay '{}': {}", host, e)))
+}
+
+impl Resolver {
+    /// Open a connection to the SMTP relay at the host and port, through the proxy determined
+    /// for it (requires the `lettre` feature.)
+    ///
+    /// The proxy is determined for an `smtps` URL if `implicit_tls` is `true`, and for an `smtp`
+    /// URL otherwise, so that __smtps_proxy__ or __smtp_proxy__, respectively, is checked before
+    /// __all_proxy__. With an HTTP proxy, a `CONNECT` tunnel to the relay is opened; with a SOCKS
+    /// proxy, the proxy is asked to connect to the relay. HTTPS proxies require the `tokio-rustls`
+    /// feature. Without a proxy, the relay is connected to directly.
+    ///
+    /// The stream carries the SMTP session, to which the caller adds TLS for implicit TLS.
+    pub async fn smtp_stream(&self, host: &str, port: u16, implicit_tls: bool) -> io::Result<AsyncProxyStream> {
+        let url = relay_url(host, port, implicit_tls)?;
+        self.connect_via_tokio(&url, host, port).await
+    }
+
+    /// Open an __lettre__ SMTP connection to the relay at the host and port, through the proxy
+    /// determined for it (requires the `lettre` feature.)
+    ///
+    /// The stream is opened as by [`smtp_stream()`](#method.smtp_stream) without implicit TLS,
+    /// and the connection is greeted with `hello_name`. It can be upgraded with `STARTTLS` if
+    /// __lettre__ has TLS support. An SMTP error is returned wrapped in an I/O error of the
+    /// `Other` kind.
+    pub async fn smtp_connection(&self, host: &str, port: u16, hello_name: &ClientId) -> io::Result<AsyncSmtpConnection> {
+        let stream = self.smtp_stream(host, port, false).await?;
+        AsyncSmtpConnection::connect_with_transport(Box::new(stream), hello_name).await
+            .map_err(io::Error::other)
+    }
+}
+
+/// Open a connection to the SMTP relay at the host and port, through the proxy from the
+/// environment (requires the `lettre` feature.)
+///
+/// The proxy is determined with the resolver of [`for_url()`](fn.for_url.html), as described
+/// for [`Resolver::smtp_stream()`](struct.Resolver.html#method.smtp_stream).
+pub async fn smtp_stream(host: &str, port: u16, implicit_tls: bool) -> io::Result<AsyncProxyStream> {
+    default_resolver().smtp_stream(host, port, implicit_tls).await
+}
+
+/// Open an __lettre__ SMTP connection to the relay at the host and port, through the proxy
+/// from the environment (requires the `lettre` feature.)
+///
+/// The proxy is determined with the resolver of [`for_url()`](fn.for_url.html), as described
+/// for [`Resolver::smtp_connection()`](struct.Resolver.html#method.smtp_connection).
+///
+/// ```no_run
+/// # extern crate env_proxy;
+/// # extern crate lettre;
+/// # async fn send() -> Result<(), Box<dyn std::error::Error>> {
+/// use lettre::transport::smtp::extension::ClientId;
+///
+/// let hello = ClientId::Domain("client.example.com".to_string());
+/// let mut connection = env_proxy::smtp_connection("mail.example.com", 587, &hello).await?;
+/// println!("{}", connection.server_info());
+/// connection.quit().await?;
+/// # Ok(())
+/// # }
+/// # fn main() {}
+/// ```
+pub async fn smtp_connection(host: &str, port: u16, hello_name: &ClientId) -> io::Result<AsyncSmtpConnection> {
+    default_resolver().smtp_connection(host, port, hello_name).await
+}
@@ -0,0 +1,180 @@
+// Copyright (c) 2016 Ivan Nejgebauer <inejge@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::env;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use url::Url;
+
+use crate::{matches_no_proxy, parse_proxy_value, NoProxy, ProviderResult, ProxyProvider};
+
+/// The port Maven assumes for a proxy without one.
+const MAVEN_DEFAULT_PROXY_PORT: u16 = 8080;
+
+/// An active `<proxy>` entry.
+#[derive(Clone, Debug)]
+struct MavenProxy {
+    protocol: String,
+    value: String,
+    non_proxy_hosts: NoProxy,
+}
+
+/// The proxies of a Maven `settings.xml` file (requires the `maven` feature.)
+///
+/// Each active `<proxy>` entry of the `<proxies>` element (`<active>` is `true` by default)
+/// has a `<protocol>` (`http` by default), a `<host>`, a `<port>` (8080 by default), optional
+/// `<username>` and `<password>`, and `<nonProxyHosts>`, a list of host names separated by `|`,
+/// which may contain wildcards and are translated as described for
+/// [`NoProxy::from_windows_override()`](struct.NoProxy.html#method.from_windows_override).
+///
+/// As in Maven, the proxy for a target is the first entry whose protocol is the target's scheme
+/// and whose `<nonProxyHosts>` don't match the target's host; for `https`, an `http` entry is
+/// used if there is no `https` one. If all entries for the scheme exclude the host, the
+/// connection is direct. Passwords encrypted with Maven's master password (enclosed in braces)
+/// can't be decrypted, and are dropped. The provider reports its proxies under the name `maven`.
+#[derive(Clone, Debug, Default)]
+pub struct MavenProxyConfig {
+    proxies: Vec<MavenProxy>,
+}
+
+impl MavenProxyConfig {
+    /// Read `.m2/settings.xml` in the home directory. If the file is missing, the configuration
+    /// is empty.
+    pub fn load() -> io::Result<MavenProxyConfig> {
+        let path = match env::var_os("HOME").filter(|home| !home.is_empty()) {
+            Some(home) => PathBuf::from(home).join(".m2").join("settings.xml"),
+            None => return Ok(MavenProxyConfig::default()),
+        };
+        match fs::read_to_string(path) {
+            Ok(contents) => MavenProxyConfig::parse(&contents),
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(MavenProxyConfig::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Parse the contents of a `settings.xml` file.
+    ///
+    /// Malformed XML is reported as an error of the `InvalidData` kind; entries without a host
+    /// are skipped.
+    pub fn parse(contents: &str) -> io::Result<MavenProxyConfig> {
+        let doc = roxmltree::Document::parse(contents).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        let child_text = |node: roxmltree::Node, name: &str| {
+            node.children()
+                .find(|child| child.has_tag_name(name))
+                .and_then(|child| child.text())
+                .map(str::trim)
+                .filter(|text| !text.is_empty())
+                .map(str::to_string)
+        };
+        let entries = doc.root_element()
+            .children()
+            .filter(|node| node.has_tag_name("proxies"))
+            .flat_map(|proxies| proxies.children().filter(|node| node.has_tag_name("proxy")));
+        let mut proxies = Vec::new();
+        for entry in entries {
+            if matches!(child_text(entry, "active").as_deref(), Some(active) if active != "true") {
+                continue;
+            }
+            let host = match child_text(entry, "host") {
+                Some(host) => host,
+                None => continue,
+            };
+            let protocol = child_text(entry, "protocol").unwrap_or_else(|| "http".to_string()).to_ascii_lowercase();
+            let port = child_text(entry, "port").and_then(|port| port.parse::<u16>().ok()).unwrap_or(MAVEN_DEFAULT_PROXY_PORT);
+            let mut value = match parse_proxy_value("maven", &format!("{}:{}", host, port), "http", None, false) {
+                Ok(url) => url,
+                Err(_) => continue,
+            };
+            if let Some(user) = child_text(entry, "username") {
+                let password = child_text(entry, "password").filter(|password| !password.starts_with('{'));
+                if value.set_username(&user).is_err() || value.set_password(password.as_deref()).is_err() {
+                    continue;
+                }
+            }
+            let non_proxy_hosts = child_text(entry, "nonProxyHosts")
+                .map(|hosts| NoProxy::from_windows_override(&hosts.replace('|', ";")))
+                .unwrap_or_default();
+            proxies.push(MavenProxy { protocol, value: value.into(), non_proxy_hosts });
+        }
+        Ok(MavenProxyConfig { proxies })
+    }
+
+    /// Return the proxy value Maven would use for the URL, if any, in canonical form.
+    pub fn proxy_for(&self, url: &Url) -> Option<&str> {
+        let applies = |proxy: &&MavenProxy| !matches_no_proxy(url, &proxy.non_proxy_hosts, None);
+        let for_protocol = |protocol: &str| self.proxies.iter().filter(|proxy| proxy.protocol == protocol).find(applies);
+        let found = match url.scheme() {
+            "https" => for_protocol("https").or_else(|| for_protocol("http")),
+            scheme => for_protocol(scheme),
+        };
+        found.map(|proxy| proxy.value.as_str())
+    }
+}
+
+impl ProxyProvider for MavenProxyConfig {
+    fn proxies_for(&self, url: &Url) -> ProviderResult {
+        if let Some(value) = self.proxy_for(url) {
+            return ProviderResult::Proxies(vec![value.to_string()]);
+        }
+        let scheme = url.scheme();
+        if self.proxies.iter().any(|proxy| proxy.protocol == scheme || (scheme == "https" && proxy.protocol == "http")) {
+            ProviderResult::Direct
+        } else {
+            ProviderResult::Pass
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        "maven"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use url::Url;
+
+    use super::MavenProxyConfig;
+    use crate::{ProviderResult, ProxyProvider};
+
+    #[test]
+    fn settings_xml() {
+        let contents = r#"<?xml version="1.0" encoding="UTF-8"?>
+<settings xmlns="http://maven.apache.org/SETTINGS/1.0.0">
+  <proxies>
+    <proxy>
+      <id>disabled</id>
+      <active>false</active>
+      <host>disabled.example.com</host>
+    </proxy>
+    <proxy>
+      <id>corporate</id>
+      <protocol>http</protocol>
+      <host>proxy.example.com</host>
+      <port>3128</port>
+      <username>builder</username>
+      <password>{COQLCE6DU6GtcS5P=}</password>
+      <nonProxyHosts>*.example.com|localhost|10.*</nonProxyHosts>
+    </proxy>
+    <proxy>
+      <protocol>ftp</protocol>
+      <host>ftp-proxy.example.com</host>
+    </proxy>
+  </proxies>
+</settings>"#;
+        let config = MavenProxyConfig::parse(contents).unwrap();
+        let proxy = |url: &str| config.proxies_for(&Url::parse(url).unwrap());
+        let proxies = |value: &str| ProviderResult::Proxies(vec![value.to_string()]);
+        assert_eq!(proxy("https://repo.maven.apache.org/maven2"), proxies("http://builder@proxy.example.com:3128/"));
+        assert_eq!(proxy("http://repo.example.com/"), ProviderResult::Direct);
+        assert_eq!(proxy("http://10.1.2.3/"), ProviderResult::Direct);
+        assert_eq!(proxy("ftp://ftp.example.org/"), proxies("http://ftp-proxy.example.com:8080/"));
+        assert_eq!(proxy("ws://ws.example.org/"), ProviderResult::Pass);
+        assert!(MavenProxyConfig::parse("<settings><proxies>").is_err());
+    }
+}
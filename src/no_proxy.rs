@@ -0,0 +1,390 @@
+// Copyright (c) 2016 Ivan Nejgebauer <inejge@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::collections::BTreeSet;
+use std::convert::Infallible;
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::str::FromStr;
+
+use crate::trace::warn;
+
+/// The entry matching plain host names, i.e., those without a dot.
+pub(crate) const LOCAL: &str = "<local>";
+
+/// Parse an entry in CIDR notation, returning the network address and the prefix length.
+///
+/// The network address is not required to have the host bits cleared.
+pub(crate) fn parse_cidr(entry: &str) -> Option<(IpAddr, u8)> {
+    let (addr, prefix) = entry.split_once('/')?;
+    let addr = addr.strip_prefix('[').and_then(|a| a.strip_suffix(']')).unwrap_or(addr);
+    let addr = addr.parse::<IpAddr>().ok()?;
+    let prefix = prefix.parse::<u8>().ok()?;
+    if prefix > width(addr) {
+        return None;
+    }
+    Some((addr, prefix))
+}
+
+fn width(addr: IpAddr) -> u8 {
+    if addr.is_ipv4() { 32 } else { 128 }
+}
+
+fn to_bits(addr: IpAddr) -> u128 {
+    match addr {
+        IpAddr::V4(addr) => u128::from(u32::from(addr)),
+        IpAddr::V6(addr) => u128::from(addr),
+    }
+}
+
+fn from_bits(bits: u128, v4: bool) -> IpAddr {
+    if v4 {
+        IpAddr::V4(Ipv4Addr::from(bits as u32))
+    } else {
+        IpAddr::V6(Ipv6Addr::from(bits))
+    }
+}
+
+/// Return the mask of the `bits` low-order bits.
+fn low_mask(bits: u8) -> u128 {
+    if bits >= 128 { u128::MAX } else { (1u128 << bits) - 1 }
+}
+
+/// Return the first and the last address of the network, as integers.
+fn cidr_range(addr: IpAddr, prefix: u8) -> (u128, u128) {
+    let host_mask = low_mask(width(addr) - prefix);
+    let start = to_bits(addr) & !host_mask;
+    (start, start | host_mask)
+}
+
+fn format_cidr(start: u128, prefix: u8, v4: bool) -> String {
+    format!("{}/{}", from_bits(start, v4), prefix)
+}
+
+/// Merge overlapping and adjacent ranges, and cover the result with a minimal set of networks.
+fn aggregate_ranges(mut ranges: Vec<(u128, u128)>, v4: bool) -> Vec<String> {
+    ranges.sort_unstable();
+    let mut merged: Vec<(u128, u128)> = Vec::new();
+    for (start, end) in ranges {
+        match merged.last_mut() {
+            Some(last) if last.1 == u128::MAX || start <= last.1 + 1 => last.1 = last.1.max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+    let width = if v4 { 32 } else { 128 };
+    let mut networks = Vec::new();
+    for (mut start, end) in merged {
+        loop {
+            let mut bits = (start.trailing_zeros() as u8).min(width);
+            while start.checked_add(low_mask(bits)).is_none_or(|last| last > end) {
+                bits -= 1;
+            }
+            networks.push(format_cidr(start, width - bits, v4));
+            match (start | low_mask(bits)).checked_add(1) {
+                Some(next) if next <= end => start = next,
+                _ => break,
+            }
+        }
+    }
+    networks
+}
+
+/// The list of hosts and domains for which no proxying should be done.
+///
+/// The list is kept in canonical form: names are lowercased, a leading dot is removed (since
+/// it doesn't affect matching, see [`for_url()`](fn.for_url.html)), networks in CIDR notation
+/// have the host bits of the address cleared, and duplicates are dropped. Two lists which match
+/// the same hosts because they differ only in the order, case, or repetition of their components
+/// will compare equal and have the same hash.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct NoProxy {
+    all: bool,
+    entries: BTreeSet<String>,
+}
+
+impl NoProxy {
+    /// Parse the value of a __no_proxy__ variable.
+    ///
+    /// The value is a space- or comma-separated list of host/domain names, IP addresses, or
+    /// networks in CIDR notation (e.g., `10.0.0.0/8` or `fd00::/8`), or a single
+    /// '&#8239;__*__&#8239;' which matches all hosts. The special entry `<local>` matches plain
+    /// host names, which don't contain a dot.
+    pub fn parse(value: &str) -> NoProxy {
+        if value == "*" {
+            return NoProxy { all: true, entries: BTreeSet::new() };
+        }
+        let entries = value.split([',', ' '])
+            .filter(|elem| !elem.is_empty() && *elem != ".")
+            .map(|elem| match parse_cidr(elem) {
+                Some((addr, prefix)) => format_cidr(cidr_range(addr, prefix).0, prefix, addr.is_ipv4()),
+                None => elem.strip_prefix('.').unwrap_or(elem).to_ascii_lowercase(),
+            })
+            .collect();
+        NoProxy { all: false, entries }
+    }
+
+    /// Translate the value of the Windows `ProxyOverride` setting.
+    ///
+    /// The value is a semicolon-separated list of host names, which may contain wildcards, and
+    /// the `<local>` token, which is kept as-is. The translation handles the common forms of
+    /// wildcard entries: a single '&#8239;__*__&#8239;' matches all hosts, a leading `*.` or `.`
+    /// is dropped (note that the resulting entry also matches the domain name itself), and an IPv4
+    /// address with trailing wildcard components, such as `10.*` or `192.168.*.*`, becomes
+    /// a network. Entries with other wildcards can't be represented, and are logged and skipped.
+    pub fn from_windows_override(value: &str) -> NoProxy {
+        let mut entries = BTreeSet::new();
+        for entry in value.split(';').map(str::trim).filter(|entry| !entry.is_empty()) {
+            if entry == "*" {
+                return NoProxy { all: true, entries: BTreeSet::new() };
+            }
+            if entry.eq_ignore_ascii_case(LOCAL) {
+                entries.insert(LOCAL.to_string());
+                continue;
+            }
+            let entry = entry.strip_prefix("*.").or_else(|| entry.strip_prefix('.')).unwrap_or(entry);
+            if !entry.contains('*') {
+                entries.extend(NoProxy::parse(entry).entries);
+                continue;
+            }
+            let octets = entry.split('.').collect::<Vec<_>>();
+            let fixed = octets.iter().take_while(|&&octet| octet != "*").count();
+            let network = if octets.len() <= 4 && fixed > 0 && octets[fixed..].iter().all(|&octet| octet == "*") {
+                let mut addr = octets[..fixed].to_vec();
+                addr.resize(4, "0");
+                parse_cidr(&format!("{}/{}", addr.join("."), fixed * 8))
+            } else {
+                None
+            };
+            match network {
+                Some((addr, prefix)) => { entries.insert(format_cidr(to_bits(addr), prefix, true)); },
+                None => warn!("unsupported ProxyOverride entry '{}'", entry),
+            }
+        }
+        NoProxy { all: false, entries }
+    }
+
+    /// Read the list from __no_proxy__ or __NO_PROXY__.
+    ///
+    /// If neither variable is defined, or the value is not valid UTF-8, the list is empty.
+    pub fn from_env() -> NoProxy {
+        env_var_pair!("no_proxy", "NO_PROXY")
+            .map(|(_, no_proxy)| NoProxy::parse(&no_proxy))
+            .unwrap_or_default()
+    }
+
+    /// Return `true` if the list disables proxying for all hosts.
+    pub fn matches_all(&self) -> bool {
+        self.all
+    }
+
+    /// Return `true` if the list contains no entries and doesn't match all hosts.
+    pub fn is_empty(&self) -> bool {
+        !self.all && self.entries.is_empty()
+    }
+
+    /// Return an iterator over the canonical entries of the list, in sorted order.
+    pub fn entries(&self) -> impl Iterator<Item = &str> {
+        self.entries.iter().map(String::as_str)
+    }
+
+    /// Return the entries in the form of libcurl's `NOPROXY` option.
+    ///
+    /// libcurl matches names like this crate, and networks since version 7.86; the `<local>`
+    /// entry can't be expressed, and is logged and skipped.
+    #[cfg(any(feature = "isahc", feature = "curl"))]
+    pub(crate) fn curl_entries(&self) -> Vec<String> {
+        if self.all {
+            return vec!["*".to_string()];
+        }
+        if self.entries.contains(LOCAL) {
+            warn!("libcurl can't match {} in its proxy bypass list; skipping", LOCAL);
+        }
+        self.entries.iter().filter(|entry| *entry != LOCAL).cloned().collect()
+    }
+
+    /// Return the union of the two lists, which matches the hosts matched by either.
+    pub fn union(&self, other: &NoProxy) -> NoProxy {
+        if self.all || other.all {
+            return NoProxy { all: true, entries: BTreeSet::new() };
+        }
+        NoProxy { all: false, entries: self.entries.union(&other.entries).cloned().collect() }
+    }
+
+    /// Return an equivalent list with redundant entries removed and networks merged.
+    ///
+    /// A name is redundant if another entry matches all hosts it matches; e.g., `www.example.org`
+    /// is redundant in the presence of `example.org`. An IP address is redundant if it's contained
+    /// in one of the networks. Overlapping and adjacent networks are merged, and the result is
+    /// covered by the minimal set of networks in CIDR notation; e.g., `10.0.0.0/9` and `10.128.0.0/9`
+    /// become `10.0.0.0/8`.
+    ///
+    /// The result matches the same hosts as the original list. This can be used to shorten very
+    /// long generated lists.
+    pub fn aggregate(&self) -> NoProxy {
+        if self.all {
+            return self.clone();
+        }
+        let (mut v4, mut v6) = (Vec::new(), Vec::new());
+        for (addr, prefix) in self.entries.iter().filter_map(|entry| parse_cidr(entry)) {
+            let range = cidr_range(addr, prefix);
+            if addr.is_ipv4() { v4.push(range) } else { v6.push(range) }
+        }
+        let in_network = |entry: &str| match entry.parse::<IpAddr>() {
+            Ok(addr) => {
+                let bits = to_bits(addr);
+                let ranges = if addr.is_ipv4() { &v4 } else { &v6 };
+                ranges.iter().any(|&(start, end)| start <= bits && bits <= end)
+            },
+            Err(_) => false,
+        };
+        let mut entries = self.entries.iter()
+            .filter(|entry| parse_cidr(entry).is_none() && !in_network(entry))
+            .filter(|entry| !self.entries.iter().any(|other| {
+                entry.len() > other.len() && entry.ends_with(other.as_str())
+                    && entry.as_bytes()[entry.len() - other.len() - 1] == b'.'
+            }))
+            .cloned()
+            .collect::<BTreeSet<_>>();
+        entries.extend(aggregate_ranges(v4, true));
+        entries.extend(aggregate_ranges(v6, false));
+        NoProxy { all: false, entries }
+    }
+
+    /// Return `true` if the list contains any networks.
+    pub fn has_networks(&self) -> bool {
+        self.entries.iter().any(|entry| parse_cidr(entry).is_some())
+    }
+
+    /// Check whether the IP address is contained in any of the networks in the list.
+    pub fn matches_addr(&self, addr: IpAddr) -> bool {
+        self.all || self.matching_network(addr).is_some()
+    }
+
+    /// Return the network entry which contains the IP address.
+    fn matching_network(&self, addr: IpAddr) -> Option<&str> {
+        self.entries.iter().find(|entry| parse_cidr(entry).is_some_and(|(net, prefix)| {
+            let (start, end) = cidr_range(net, prefix);
+            net.is_ipv4() == addr.is_ipv4() && (start..=end).contains(&to_bits(addr))
+        })).map(String::as_str)
+    }
+
+    /// Check whether the host matches the list.
+    ///
+    /// The host matches an entry if it's equal to it, or if it ends with the entry and the part
+    /// before it ends with a dot. Entries are compared case-insensitively. A host which is an IP
+    /// address (IPv6 addresses may be enclosed in brackets) also matches a network entry which
+    /// contains the address. A host which is neither an IP address nor contains a dot matches
+    /// the `<local>` entry.
+    pub fn matches(&self, host: &str) -> bool {
+        self.matching_entry(host).is_some()
+    }
+
+    /// Return the entry matched by the host, as described for [`matches()`](#method.matches),
+    /// or `*` if the list matches all hosts.
+    pub(crate) fn matching_entry(&self, host: &str) -> Option<&str> {
+        if self.all {
+            return Some("*");
+        }
+        let addr = host.strip_prefix('[').and_then(|h| h.strip_suffix(']')).unwrap_or(host).parse::<IpAddr>();
+        if let Some(network) = addr.as_ref().ok().and_then(|&addr| self.matching_network(addr)) {
+            return Some(network);
+        }
+        if addr.is_err() && !host.contains('.') && self.entries.contains(LOCAL) {
+            return Some(LOCAL);
+        }
+        self.entries.iter().filter(|entry| parse_cidr(entry).is_none()).find(|entry| {
+            let host_bytes = host.as_bytes();
+            let entry_bytes = entry.as_bytes();
+            if entry_bytes.len() > host_bytes.len() {
+                return false;
+            }
+            let split = host_bytes.len() - entry_bytes.len();
+            host_bytes[split..].eq_ignore_ascii_case(entry_bytes) && (split == 0 || host_bytes[split - 1] == b'.')
+        }).map(String::as_str)
+    }
+}
+
+impl fmt::Display for NoProxy {
+    /// Format the list in canonical form, as a comma-separated value.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.all {
+            return f.write_str("*");
+        }
+        for (i, entry) in self.entries.iter().enumerate() {
+            if i > 0 {
+                f.write_str(",")?;
+            }
+            f.write_str(entry)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for NoProxy {
+    type Err = Infallible;
+
+    /// Parse a __no_proxy__ value, as [`parse()`](#method.parse) does, e.g., from a `--noproxy`
+    /// command-line option.
+    fn from_str(value: &str) -> Result<NoProxy, Infallible> {
+        Ok(NoProxy::parse(value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::NoProxy;
+
+    #[test]
+    fn canonical_form() {
+        let a = NoProxy::parse("Example.ORG, .example.org,,localhost .");
+        let b = NoProxy::parse("localhost,example.org");
+        assert_eq!(a, b);
+        assert_eq!(a.to_string(), "example.org,localhost");
+        assert!(a.matches("www.example.org"));
+        assert!(a.matches("WWW.Example.ORG"));
+        assert!(!a.matches("xample.org"));
+        assert!(NoProxy::parse("*").matches("anything"));
+        assert!(NoProxy::parse("").is_empty());
+    }
+
+    #[test]
+    fn networks() {
+        let list = NoProxy::parse("10.1.2.3/8,[fd00::1]/8");
+        assert_eq!(list.to_string(), "10.0.0.0/8,fd00::/8");
+        assert!(list.matches("10.20.30.40"));
+        assert!(list.matches("[fd12::1]"));
+        assert!(!list.matches("11.0.0.1"));
+        assert!(!list.matches("10.example.org"));
+    }
+
+    #[test]
+    fn aggregate_networks() {
+        let list = NoProxy::parse("10.0.0.0/9,10.128.0.0/9,10.1.2.3,192.168.1.0/24,192.168.2.0/24,192.168.0.0/24,::/1,8000::/1");
+        assert_eq!(list.aggregate().to_string(), "10.0.0.0/8,192.168.0.0/23,192.168.2.0/24,::/0");
+    }
+
+    #[test]
+    fn windows_override() {
+        let list = NoProxy::from_windows_override("*.Example.org; 10.*;192.168.1.*;<LOCAL>;*foo*;;intranet");
+        assert_eq!(list.to_string(), "10.0.0.0/8,192.168.1.0/24,<local>,example.org,intranet");
+        assert!(list.matches("printer"));
+        assert!(list.matches("10.1.2.3"));
+        assert!(!list.matches("192.168.2.1"));
+        assert!(!list.matches("[::1]"));
+        assert!(!list.matches("www.example.com"));
+        assert_eq!(list.matching_entry("printer"), Some("<local>"));
+        assert_eq!(list.matching_entry("10.1.2.3"), Some("10.0.0.0/8"));
+        assert_eq!(list.matching_entry("www.example.org"), Some("example.org"));
+        assert!(NoProxy::from_windows_override("<local>;*").matches_all());
+    }
+
+    #[test]
+    fn aggregate_suffixes() {
+        let list = NoProxy::parse("www.example.org,example.org,a.b.example.org,xample.org,org.net");
+        assert_eq!(list.aggregate().to_string(), "example.org,org.net,xample.org");
+    }
+}
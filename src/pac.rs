@@ -0,0 +1,59 @@
+// Copyright (c) 2016 Ivan Nejgebauer <inejge@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use crate::trace::warn;
+
+/// Translate the result of `FindProxyForURL()` into an ordered list of proxy values.
+///
+/// The result is a semicolon-separated list of `PROXY host:port`, `HTTP host:port`,
+/// `HTTPS host:port`, `SOCKS host:port`, `SOCKS4 host:port`, `SOCKS5 host:port` and `DIRECT`
+/// entries. A proxy entry becomes a URL with the corresponding scheme, where `PROXY` means HTTP
+/// and `SOCKS` means SOCKS4, as in most browsers; `DIRECT` becomes `None`. Unknown entries are
+/// skipped with a warning.
+pub(crate) fn parse_pac_result(result: &str) -> Vec<Option<String>> {
+    let mut proxies = Vec::new();
+    for entry in result.split(';').map(str::trim).filter(|entry| !entry.is_empty()) {
+        let mut parts = entry.split_whitespace();
+        let kind = parts.next().unwrap_or("");
+        if kind.eq_ignore_ascii_case("DIRECT") {
+            proxies.push(None);
+            continue;
+        }
+        let scheme = match kind.to_ascii_uppercase().as_str() {
+            "PROXY" | "HTTP" => "http",
+            "HTTPS" => "https",
+            "SOCKS" | "SOCKS4" => "socks4",
+            "SOCKS5" => "socks5",
+            _ => "",
+        };
+        match (scheme, parts.next(), parts.next()) {
+            ("", _, _) | (_, None, _) | (_, _, Some(_)) => warn!("unsupported PAC result entry '{}'", entry),
+            (scheme, Some(endpoint), None) => proxies.push(Some(format!("{}://{}", scheme, endpoint))),
+        }
+    }
+    proxies
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_pac_result;
+
+    #[test]
+    fn pac_result() {
+        assert_eq!(
+            parse_pac_result("PROXY proxy.example.com:3128; HTTPS [::1]:443;SOCKS5 socks:1080 ; DIRECT; BOGUS x"),
+            vec![
+                Some("http://proxy.example.com:3128".to_string()),
+                Some("https://[::1]:443".to_string()),
+                Some("socks5://socks:1080".to_string()),
+                None,
+            ]
+        );
+        assert_eq!(parse_pac_result("socks h:1; PROXY"), vec![Some("socks4://h:1".to_string())]);
+        assert!(parse_pac_result("").is_empty());
+    }
+}
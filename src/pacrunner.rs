@@ -0,0 +1,186 @@
+// Copyright (c) 2016 Ivan Nejgebauer <inejge@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::collections::HashMap;
+use std::io::{self, Read};
+use std::process::{Command, Stdio};
+use std::sync::{Mutex, OnceLock};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use url::Url;
+
+use crate::pac::parse_pac_result;
+
+/// The name under which the proxies returned by PacRunner are reported.
+pub(crate) const PACRUNNER_VAR: &str = "pacrunner";
+
+/// How long the answer for a target's origin is reused.
+const ANSWER_TTL: Duration = Duration::from_secs(30);
+
+/// How long a call may take before `dbus-send` is killed.
+const CALL_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How long PacRunner isn't asked again after a failed call.
+const RETRY_AFTER: Duration = Duration::from_secs(60);
+
+/// The maximum number of answers kept.
+const MAX_ANSWERS: usize = 256;
+
+/// The answers of PacRunner by target origin, and its availability.
+#[derive(Default)]
+struct State {
+    answers: HashMap<String, (Instant, Vec<Option<String>>)>,
+    missing: bool,
+    retry_at: Option<Instant>,
+}
+
+/// The outcome of a call to PacRunner: its answer, or `None` if the service isn't available.
+type CallResult = io::Result<Option<Vec<Option<String>>>>;
+
+/// Ask PacRunner for the proxies to use for the URL, in order of preference.
+///
+/// The `FindProxyForURL` method of `org.pacrunner.Client` is called on the system bus with
+/// `dbus-send`. Credentials are removed from the URL before it's sent. `None` in the result
+/// means a direct connection. The answer is reused for the target's scheme, host and port
+/// for 30 seconds. If `dbus-send` isn't installed, the result is always empty; if the call
+/// fails, e.g., because the service isn't running, or takes longer than two seconds, the result
+/// is empty, and PacRunner isn't asked again for a minute. Only an error other than the service
+/// being unavailable is returned, so that it's reported once.
+pub(crate) fn find_proxy(url: &Url) -> io::Result<Vec<Option<String>>> {
+    static STATE: OnceLock<Mutex<State>> = OnceLock::new();
+    find_proxy_with(STATE.get_or_init(Mutex::default), url, call)
+}
+
+fn find_proxy_with(state: &Mutex<State>, url: &Url, call: fn(&Url) -> CallResult) -> io::Result<Vec<Option<String>>> {
+    let origin = format!("{}://{}:{}", url.scheme(), url.host_str().unwrap_or(""), url.port_or_known_default().unwrap_or(0));
+    let now = Instant::now();
+    {
+        let state = state.lock().unwrap_or_else(|e| e.into_inner());
+        if state.missing || state.retry_at.is_some_and(|at| now < at) {
+            return Ok(Vec::new());
+        }
+        if let Some((answered, proxies)) = state.answers.get(&origin) {
+            if now.duration_since(*answered) < ANSWER_TTL {
+                return Ok(proxies.clone());
+            }
+        }
+    }
+    // the lock isn't held during the call, so that a slow service delays only the resolutions
+    // which need it
+    let result = call(url);
+    let mut state = state.lock().unwrap_or_else(|e| e.into_inner());
+    match result {
+        Ok(Some(proxies)) => {
+            state.retry_at = None;
+            if state.answers.len() >= MAX_ANSWERS {
+                state.answers.retain(|_, (answered, _)| now.duration_since(*answered) < ANSWER_TTL);
+                if state.answers.len() >= MAX_ANSWERS {
+                    state.answers.clear();
+                }
+            }
+            state.answers.insert(origin, (now, proxies.clone()));
+            Ok(proxies)
+        },
+        Ok(None) => {
+            state.retry_at = Some(now + RETRY_AFTER);
+            Ok(Vec::new())
+        },
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => {
+            state.missing = true;
+            Ok(Vec::new())
+        },
+        Err(e) => {
+            state.retry_at = Some(now + RETRY_AFTER);
+            Err(e)
+        },
+    }
+}
+
+/// Call PacRunner with `dbus-send`, killing it if it doesn't finish in time.
+fn call(url: &Url) -> CallResult {
+    let mut url = url.clone();
+    let _ = url.set_username("");
+    let _ = url.set_password(None);
+    let mut child = Command::new("dbus-send")
+        .args(["--system", "--print-reply=literal", "--dest=org.pacrunner"])
+        .arg(format!("--reply-timeout={}", CALL_TIMEOUT.as_millis()))
+        .arg("/org/pacrunner/client")
+        .arg("org.pacrunner.Client.FindProxyForURL")
+        .arg(format!("string:{}", url))
+        .arg(format!("string:{}", url.host_str().unwrap_or("")))
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()?;
+    let deadline = Instant::now() + CALL_TIMEOUT;
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if Instant::now() >= deadline {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(io::Error::new(io::ErrorKind::TimedOut, "PacRunner didn't answer in time"));
+        }
+        thread::sleep(Duration::from_millis(5));
+    };
+    if !status.success() {
+        return Ok(None);
+    }
+    let mut output = Vec::new();
+    if let Some(mut stdout) = child.stdout.take() {
+        stdout.read_to_end(&mut output)?;
+    }
+    Ok(Some(parse_pac_result(&String::from_utf8_lossy(&output))))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Mutex;
+
+    use url::Url;
+
+    use super::{find_proxy_with, CallResult, State};
+
+    static CALLS: AtomicUsize = AtomicUsize::new(0);
+
+    fn answering(_url: &Url) -> CallResult {
+        CALLS.fetch_add(1, Ordering::SeqCst);
+        Ok(Some(vec![Some("PROXY pac.example.com:3128".to_string()), None]))
+    }
+
+    fn failing(_url: &Url) -> CallResult {
+        Err(io::Error::new(io::ErrorKind::TimedOut, "PacRunner didn't answer in time"))
+    }
+
+    fn missing(_url: &Url) -> CallResult {
+        Err(io::Error::new(io::ErrorKind::NotFound, "no dbus-send"))
+    }
+
+    #[test]
+    fn answers_reused_and_failures_not_retried() {
+        let state = Mutex::new(State::default());
+        let url = Url::parse("http://www.example.com/a").unwrap();
+        assert_eq!(find_proxy_with(&state, &url, answering).unwrap().len(), 2);
+        let other_path = Url::parse("http://www.example.com/b").unwrap();
+        assert_eq!(find_proxy_with(&state, &other_path, answering).unwrap().len(), 2);
+        assert_eq!(CALLS.load(Ordering::SeqCst), 1);
+        let other_host = Url::parse("http://www.example.org/").unwrap();
+        assert!(find_proxy_with(&state, &other_host, failing).is_err());
+        assert_eq!(find_proxy_with(&state, &other_host, answering).unwrap(), Vec::new());
+        assert_eq!(CALLS.load(Ordering::SeqCst), 1);
+
+        let state = Mutex::new(State::default());
+        assert_eq!(find_proxy_with(&state, &url, missing).unwrap(), Vec::new());
+        assert!(state.lock().unwrap().missing);
+        assert_eq!(find_proxy_with(&state, &url, answering).unwrap(), Vec::new());
+        assert_eq!(CALLS.load(Ordering::SeqCst), 1);
+    }
+}
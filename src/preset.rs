@@ -0,0 +1,173 @@
+// Copyright (c) 2016 Ivan Nejgebauer <inejge@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::fmt;
+
+use url::Url;
+
+use crate::Resolver;
+
+/// A compatibility preset, matching the assumptions of another proxy consumer.
+///
+/// A preset configures a [`Resolver`](struct.Resolver.html) so that values written for the
+/// named consumer are interpreted the same way, as far as the resolver's settings allow. The
+/// only preset is __curl__, whose variable set and __no_proxy__ matching are those described
+/// for [`for_url()`](fn.for_url.html); it differs from the default resolver only in assuming
+/// port 1080 for proxy values which don't specify one. Other consumers read different variables
+/// and use per-scheme default ports, which the resolver doesn't model, so they have no preset.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Preset {
+    /// The __curl__ command-line tool and library.
+    Curl,
+}
+
+/// The (variables, target) cases of the snapshot corpus.
+const CORPUS: &[(&[(&str, &str)], &str)] = &[
+    (&[("http_proxy", "proxy.example.com")], "http://www.example.org/"),
+    (&[("http_proxy", "proxy.example.com:3128")], "http://www.example.org/"),
+    (&[("http_proxy", "socks5h://proxy.example.com")], "http://www.example.org/"),
+    (&[("https_proxy", "https://secure.example.com")], "https://www.example.org/"),
+    (&[("https_proxy", "https://secure.example.com:3129"), ("all_proxy", "proxy.example.com:3128")], "ftp://ftp.example.org/"),
+    (&[("all_proxy", "proxy.example.com:3128")], "wss://www.example.org/"),
+    (&[("HTTP_PROXY", "proxy.example.com:3128")], "http://www.example.org/"),
+    (&[("http_proxy", "proxy.example.com:3128"), ("no_proxy", "example.org")], "http://www.example.org/"),
+    (&[("http_proxy", "proxy.example.com:3128"), ("no_proxy", ".example.org")], "http://example.org/"),
+    (&[("http_proxy", "proxy.example.com:3128"), ("no_proxy", "example.org")], "http://xample.org/"),
+    (&[("http_proxy", "proxy.example.com:3128"), ("no_proxy", "*")], "http://www.example.org/"),
+    (&[("http_proxy", "proxy.example.com:3128"), ("no_proxy", "10.0.0.0/8")], "http://10.1.2.3/"),
+    (&[("http_proxy", "proxy.example.com:3128"), ("proxy_user", "alice"), ("proxy_password", "secret")], "http://www.example.org/"),
+    (&[("all_proxy", "proxy.example.com:3128")], "http://proxy.example.com:3128/"),
+];
+
+/// The outcome of a single case in a [`Snapshot`](struct.Snapshot.html).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SnapshotEntry {
+    vars: String,
+    target: String,
+    decision: String,
+}
+
+impl fmt::Display for SnapshotEntry {
+    /// Format the entry as a line of the snapshot table.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} | {} => {}", self.vars, self.target, self.decision)
+    }
+}
+
+/// The decisions of a preset for a fixed corpus of configurations and targets.
+///
+/// The snapshot is rendered as a table with one line per case, in the form
+/// `VARIABLES | TARGET => DECISION`, where the decision is the proxy URL, with the password
+/// redacted, or `DIRECT`. The tables for the current version are published with the crate,
+/// and are available through [`Preset::published()`](enum.Preset.html#method.published).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Snapshot {
+    entries: Vec<SnapshotEntry>,
+}
+
+impl Snapshot {
+    /// Return the entries of the snapshot.
+    pub fn entries(&self) -> &[SnapshotEntry] {
+        &self.entries
+    }
+
+    /// Compare the snapshot with a published table, returning the lines which differ.
+    ///
+    /// Each returned line is prefixed with `-` if it's only in the published table, or `+` if
+    /// it's only in the snapshot. An empty result means that the behavior is unchanged.
+    pub fn diff(&self, published: &str) -> Vec<String> {
+        let current = self.entries.iter().map(ToString::to_string).collect::<Vec<_>>();
+        let published = published.lines().filter(|line| !line.trim().is_empty()).collect::<Vec<_>>();
+        published.iter()
+            .filter(|&&line| !current.iter().any(|c| c == line))
+            .map(|line| format!("-{}", line))
+            .chain(current.iter().filter(|c| !published.contains(&c.as_str())).map(|line| format!("+{}", line)))
+            .collect()
+    }
+}
+
+impl fmt::Display for Snapshot {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for entry in &self.entries {
+            writeln!(f, "{}", entry)?;
+        }
+        Ok(())
+    }
+}
+
+impl Preset {
+    /// Return the name of the preset.
+    pub fn name(self) -> &'static str {
+        match self {
+            Preset::Curl => "curl",
+        }
+    }
+
+    /// Return the preset with the given name, as returned by [`name()`](#method.name).
+    pub fn from_name(name: &str) -> Option<Preset> {
+        [Preset::Curl].iter().cloned().find(|preset| preset.name() == name)
+    }
+
+    /// Return a resolver configured for the preset.
+    pub fn resolver(self) -> Resolver {
+        match self {
+            Preset::Curl => Resolver::new().with_default_port(1080),
+        }
+    }
+
+    /// Run the snapshot corpus through the preset's resolver.
+    ///
+    /// The environment is not consulted. Compare the result with a table saved from a previous
+    /// version, or with [`published()`](#method.published), to verify that an upgrade didn't
+    /// change the decisions.
+    pub fn snapshot(self) -> Snapshot {
+        let resolver = self.resolver();
+        let entries = CORPUS.iter()
+            .map(|&(vars, target)| {
+                let url = Url::parse(target).expect("valid corpus URL");
+                let decision = resolver.clone().with_vars(vars.iter().cloned())
+                    .for_url(&url)
+                    .to_string_redacted()
+                    .unwrap_or_else(|| "DIRECT".to_string());
+                SnapshotEntry {
+                    vars: vars.iter().map(|(k, v)| format!("{}={}", k, v)).collect::<Vec<_>>().join(" "),
+                    target: target.to_string(),
+                    decision,
+                }
+            })
+            .collect();
+        Snapshot { entries }
+    }
+
+    /// Return the snapshot table published with this version of the crate.
+    pub fn published(self) -> &'static str {
+        match self {
+            Preset::Curl => include_str!("../golden/curl.txt"),
+        }
+    }
+}
+
+impl fmt::Display for Preset {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Preset;
+
+    #[test]
+    fn golden_snapshots() {
+        let snapshot = Preset::Curl.snapshot();
+        assert_eq!(snapshot.diff(Preset::Curl.published()), Vec::<String>::new());
+        assert_eq!(snapshot.to_string(), Preset::Curl.published());
+        assert_eq!(Preset::from_name("curl"), Some(Preset::Curl));
+        assert_eq!(Preset::from_name("wget"), None);
+    }
+}
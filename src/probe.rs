@@ -0,0 +1,316 @@
+// Copyright (c) 2016 Ivan Nejgebauer <inejge@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::error;
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
+use std::time::{Duration, Instant};
+
+use url::Url;
+
+use crate::tunnel::{check_connect_response, connect_request, HeaderReader};
+use crate::{socks, Error, ProxyKind, ProxyUrl};
+
+/// The reason why a proxy failed a [probe](struct.ProxyUrl.html#method.probe) (requires the
+/// `probe` feature.)
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ProbeError {
+    /// There is no proxy to probe.
+    NoProxy,
+    /// The proxy value can't be turned into a URL.
+    Invalid(Error),
+    /// The proxy host name can't be resolved.
+    Resolve(io::Error),
+    /// The proxy host refused the connection; nothing listens on the port.
+    Refused,
+    /// The proxy didn't respond in time.
+    TimedOut,
+    /// The TLS handshake with an HTTPS proxy failed.
+    Tls(io::Error),
+    /// The proxy wants credentials, or didn't accept the ones it was given.
+    AuthenticationRequired,
+    /// The proxy didn't open the connection to the target; `status` is the HTTP status code
+    /// of the response to `CONNECT`, and `message` the status line or the SOCKS reply.
+    Rejected { status: Option<u16>, message: String },
+    /// The proxy's response doesn't follow the proxy protocol; the message describes it.
+    Protocol(String),
+    /// The check can't be made for the proxy's scheme without an additional feature, or at all.
+    Unsupported(String),
+    /// The connection failed or broke for another reason.
+    Io(io::Error),
+}
+
+impl fmt::Display for ProbeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ProbeError::NoProxy => write!(f, "no proxy to probe"),
+            ProbeError::Invalid(ref e) => write!(f, "invalid proxy: {}", e),
+            ProbeError::Resolve(ref e) => write!(f, "can't resolve the proxy host: {}", e),
+            ProbeError::Refused => write!(f, "the proxy refused the connection"),
+            ProbeError::TimedOut => write!(f, "the proxy didn't respond in time"),
+            ProbeError::Tls(ref e) => write!(f, "TLS handshake with the proxy failed: {}", e),
+            ProbeError::AuthenticationRequired => write!(f, "the proxy requires authentication"),
+            ProbeError::Rejected { ref message, .. } => write!(f, "the proxy rejected the connection: {}", message),
+            ProbeError::Protocol(ref message) => write!(f, "unexpected proxy response: {}", message),
+            ProbeError::Unsupported(ref message) => write!(f, "can't probe the proxy: {}", message),
+            ProbeError::Io(ref e) => write!(f, "proxy connection failed: {}", e),
+        }
+    }
+}
+
+impl error::Error for ProbeError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            ProbeError::Invalid(ref e) => Some(e),
+            ProbeError::Resolve(ref e) | ProbeError::Tls(ref e) | ProbeError::Io(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// Classify an error of the connection to the proxy.
+fn io_error(e: io::Error) -> ProbeError {
+    match e.kind() {
+        io::ErrorKind::ConnectionRefused => ProbeError::Refused,
+        io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock => ProbeError::TimedOut,
+        _ => ProbeError::Io(e),
+    }
+}
+
+/// Classify an error of the TLS handshake with the proxy.
+#[cfg(feature = "rustls")]
+fn tls_error(e: io::Error) -> ProbeError {
+    match e.kind() {
+        io::ErrorKind::InvalidData => ProbeError::Tls(e),
+        _ => io_error(e),
+    }
+}
+
+/// Classify an error of the SOCKS handshake.
+fn socks_error(e: io::Error) -> ProbeError {
+    match e.kind() {
+        io::ErrorKind::PermissionDenied => ProbeError::AuthenticationRequired,
+        io::ErrorKind::Other => ProbeError::Rejected { status: None, message: e.to_string() },
+        io::ErrorKind::InvalidData => ProbeError::Protocol(e.to_string()),
+        io::ErrorKind::Unsupported | io::ErrorKind::InvalidInput => ProbeError::Unsupported(e.to_string()),
+        _ => io_error(e),
+    }
+}
+
+/// Check the proxy's response header to `CONNECT`.
+fn connect_result(header: &[u8]) -> Result<(), ProbeError> {
+    let status_line = String::from_utf8_lossy(header).lines().next().unwrap_or("").to_string();
+    match check_connect_response(header) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == io::ErrorKind::InvalidData => Err(ProbeError::Protocol(status_line)),
+        Err(_) => match status_line.split(' ').nth(1).and_then(|status| status.parse().ok()) {
+            Some(407) => Err(ProbeError::AuthenticationRequired),
+            status => Err(ProbeError::Rejected { status, message: status_line }),
+        },
+    }
+}
+
+/// Send `CONNECT` for the target authority (__host:port__) and check the response.
+fn tunnel_check<S: Read + Write>(stream: &mut S, proxy: &Url, authority: &str) -> Result<(), ProbeError> {
+    stream.write_all(connect_request(proxy, authority).as_bytes()).map_err(io_error)?;
+    stream.flush().map_err(io_error)?;
+    let mut reader = HeaderReader::default();
+    let mut byte = [0u8];
+    loop {
+        if stream.read(&mut byte).map_err(io_error)? == 0 {
+            return Err(ProbeError::Protocol("the proxy closed the connection".to_string()));
+        }
+        if reader.push(byte[0]).map_err(|e| ProbeError::Protocol(e.to_string()))? {
+            return connect_result(&reader.header);
+        }
+    }
+}
+
+/// Return the time left until the deadline, or the timeout error if it has passed.
+fn remaining(deadline: Instant) -> Result<Duration, ProbeError> {
+    deadline.checked_duration_since(Instant::now())
+        .filter(|left| !left.is_zero())
+        .ok_or(ProbeError::TimedOut)
+}
+
+/// Return the proxy host, unbracketed, and port.
+fn proxy_host_port(proxy: &Url) -> (&str, u16) {
+    let host = proxy.host_str().unwrap_or("").trim_start_matches('[').trim_end_matches(']');
+    (host, proxy.port_or_known_default().unwrap_or(0))
+}
+
+/// Format the target authority for `CONNECT`.
+fn authority(host: &str, port: u16) -> String {
+    if host.contains(':') && !host.starts_with('[') {
+        format!("[{}]:{}", host, port)
+    } else {
+        format!("{}:{}", host, port)
+    }
+}
+
+impl ProxyUrl {
+    fn probe_url(&self) -> Result<Url, ProbeError> {
+        match self.resolved_url() {
+            Ok(Some(url)) => Ok(url.into_owned()),
+            Ok(None) => Err(ProbeError::NoProxy),
+            Err(e) => Err(ProbeError::Invalid(e)),
+        }
+    }
+
+    /// Check that the proxy accepts connections, giving up after the timeout (requires the
+    /// `probe` feature.)
+    ///
+    /// The proxy host is resolved, and a TCP connection is opened to each of its addresses in
+    /// turn until one succeeds; nothing is sent over it. Name resolution isn't covered by the
+    /// timeout. The proxy URL is transformed as by [`to_url()`](#method.to_url), and the
+    /// failure, if any, is reported as a [`ProbeError`](enum.ProbeError.html).
+    ///
+    /// ```no_run
+    /// # extern crate env_proxy;
+    /// # extern crate url;
+    /// # use std::time::Duration;
+    /// let url = url::Url::parse("https://www.example.com/").unwrap();
+    /// match env_proxy::for_url(&url).probe_tunnel("www.example.com", 443, Duration::from_secs(5)) {
+    ///     Ok(()) => println!("proxy OK"),
+    ///     Err(env_proxy::ProbeError::NoProxy) => println!("no proxy"),
+    ///     Err(e) => println!("proxy check failed: {}", e),
+    /// }
+    /// ```
+    pub fn probe(&self, timeout: Duration) -> Result<(), ProbeError> {
+        self.probe_connection(timeout).map(drop)
+    }
+
+    /// Check that the proxy opens connections to the target host and port, giving up after
+    /// the timeout (requires the `probe` feature.)
+    ///
+    /// After connecting as [`probe()`](#method.probe) does, an HTTP proxy is sent a `CONNECT`
+    /// request for the target, authorized with the proxy credentials, if any, and a SOCKS proxy
+    /// is asked to connect to the target; the check succeeds if the proxy agrees. The tunnel
+    /// isn't used, so any host reachable through the proxy will do. An HTTPS proxy is checked
+    /// over TLS, configured as for [`Resolver::connect()`](struct.Resolver.html#method.connect),
+    /// which requires the `rustls` feature. The timeout covers the connection, and separately
+    /// each read and write of the check.
+    pub fn probe_tunnel(&self, host: &str, port: u16, timeout: Duration) -> Result<(), ProbeError> {
+        let (proxy, mut stream) = self.probe_connection(timeout)?;
+        stream.set_read_timeout(Some(timeout)).map_err(io_error)?;
+        stream.set_write_timeout(Some(timeout)).map_err(io_error)?;
+        match ProxyKind::from_scheme(proxy.scheme()) {
+            Some(ProxyKind::Http) => tunnel_check(&mut stream, &proxy, &authority(host, port)),
+            #[cfg(feature = "rustls")]
+            Some(ProxyKind::Https) => {
+                let (proxy_host, _) = proxy_host_port(&proxy);
+                let mut stream = crate::Resolver::new().proxy_tls_handshake(stream, proxy_host).map_err(tls_error)?;
+                tunnel_check(&mut stream, &proxy, &authority(host, port))
+            },
+            #[cfg(not(feature = "rustls"))]
+            Some(ProxyKind::Https) => Err(ProbeError::Unsupported("HTTPS proxies require the rustls feature".to_string())),
+            Some(_) => socks::handshake(&mut stream, &proxy, host, port).map_err(socks_error),
+            None => Err(ProbeError::Unsupported(format!("unsupported proxy scheme '{}'", proxy.scheme()))),
+        }
+    }
+
+    fn probe_connection(&self, timeout: Duration) -> Result<(Url, TcpStream), ProbeError> {
+        let proxy = self.probe_url()?;
+        let addrs = proxy_host_port(&proxy).to_socket_addrs().map_err(ProbeError::Resolve)?.collect::<Vec<SocketAddr>>();
+        let deadline = Instant::now() + timeout;
+        let mut last_error = None;
+        for addr in addrs {
+            match TcpStream::connect_timeout(&addr, remaining(deadline)?) {
+                Ok(stream) => return Ok((proxy, stream)),
+                Err(e) => last_error = Some(e),
+            }
+        }
+        match last_error {
+            Some(e) => Err(io_error(e)),
+            None => Err(ProbeError::Resolve(io::Error::new(io::ErrorKind::NotFound, "no addresses for the proxy host"))),
+        }
+    }
+
+    /// Check that the proxy accepts connections, without blocking (requires the `probe` and
+    /// `tokio` features.)
+    ///
+    /// See [`probe()`](#method.probe). The timeout covers name resolution as well; the runtime
+    /// must have the time driver enabled.
+    #[cfg(feature = "tokio")]
+    pub async fn probe_async(&self, timeout: Duration) -> Result<(), ProbeError> {
+        let proxy = self.probe_url()?;
+        match tokio::time::timeout(timeout, probe_connection_tokio(&proxy)).await {
+            Ok(result) => result.map(drop),
+            Err(_) => Err(ProbeError::TimedOut),
+        }
+    }
+
+    /// Check that the proxy opens connections to the target host and port, without blocking
+    /// (requires the `probe` and `tokio` features.)
+    ///
+    /// See [`probe_tunnel()`](#method.probe_tunnel). The timeout covers the whole check, and
+    /// HTTPS proxies require the `tokio-rustls` feature.
+    #[cfg(feature = "tokio")]
+    pub async fn probe_tunnel_async(&self, host: &str, port: u16, timeout: Duration) -> Result<(), ProbeError> {
+        let proxy = self.probe_url()?;
+        match tokio::time::timeout(timeout, probe_tunnel_tokio(&proxy, host, port)).await {
+            Ok(result) => result,
+            Err(_) => Err(ProbeError::TimedOut),
+        }
+    }
+}
+
+#[cfg(feature = "tokio")]
+async fn probe_connection_tokio(proxy: &Url) -> Result<tokio::net::TcpStream, ProbeError> {
+    let addrs = tokio::net::lookup_host(proxy_host_port(proxy)).await.map_err(ProbeError::Resolve)?;
+    let mut last_error = None;
+    for addr in addrs {
+        match tokio::net::TcpStream::connect(addr).await {
+            Ok(stream) => return Ok(stream),
+            Err(e) => last_error = Some(e),
+        }
+    }
+    match last_error {
+        Some(e) => Err(io_error(e)),
+        None => Err(ProbeError::Resolve(io::Error::new(io::ErrorKind::NotFound, "no addresses for the proxy host"))),
+    }
+}
+
+#[cfg(feature = "tokio")]
+async fn tunnel_check_tokio<S>(stream: &mut S, proxy: &Url, authority: &str) -> Result<(), ProbeError>
+    where S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin
+{
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    stream.write_all(connect_request(proxy, authority).as_bytes()).await.map_err(io_error)?;
+    stream.flush().await.map_err(io_error)?;
+    let mut reader = HeaderReader::default();
+    let mut byte = [0u8];
+    loop {
+        if stream.read(&mut byte).await.map_err(io_error)? == 0 {
+            return Err(ProbeError::Protocol("the proxy closed the connection".to_string()));
+        }
+        if reader.push(byte[0]).map_err(|e| ProbeError::Protocol(e.to_string()))? {
+            return connect_result(&reader.header);
+        }
+    }
+}
+
+#[cfg(feature = "tokio")]
+async fn probe_tunnel_tokio(proxy: &Url, host: &str, port: u16) -> Result<(), ProbeError> {
+    let mut stream = probe_connection_tokio(proxy).await?;
+    match ProxyKind::from_scheme(proxy.scheme()) {
+        Some(ProxyKind::Http) => tunnel_check_tokio(&mut stream, proxy, &authority(host, port)).await,
+        #[cfg(feature = "tokio-rustls")]
+        Some(ProxyKind::Https) => {
+            let (proxy_host, _) = proxy_host_port(proxy);
+            let mut stream = crate::Resolver::new().proxy_tls_handshake_tokio(stream, proxy_host).await.map_err(tls_error)?;
+            tunnel_check_tokio(&mut stream, proxy, &authority(host, port)).await
+        },
+        #[cfg(not(feature = "tokio-rustls"))]
+        Some(ProxyKind::Https) => Err(ProbeError::Unsupported("HTTPS proxies require the tokio-rustls feature".to_string())),
+        Some(_) => socks::handshake_tokio(&mut stream, proxy, host, port).await.map_err(socks_error),
+        None => Err(ProbeError::Unsupported(format!("unsupported proxy scheme '{}'", proxy.scheme()))),
+    }
+}
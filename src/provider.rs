@@ -0,0 +1,173 @@
+// Copyright (c) 2016 Ivan Nejgebauer <inejge@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::fmt;
+use std::sync::Arc;
+
+use url::Url;
+
+/// The answer of a [`ProxyProvider`](trait.ProxyProvider.html) for a target URL.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ProviderResult {
+    /// The provider has no information about the target; the next provider is consulted.
+    Pass,
+    /// The target should be connected to directly.
+    Direct,
+    /// The proxy values to use for the target, in order of preference. The values are
+    /// interpreted like those of the proxy variables.
+    Proxies(Vec<String>),
+}
+
+/// A source of proxy settings.
+///
+/// The sources built into the crate are the environment and, with the appropriate features, the
+/// settings of the operating system and PacRunner. Other sources, e.g., the configuration files
+/// of a corporate security agent, can be plugged into a resolver by implementing this trait and
+/// adding the implementation to a [`ProviderChain`](struct.ProviderChain.html). A closure taking
+/// a `&Url` and returning a `ProviderResult` is a provider.
+pub trait ProxyProvider: Send + Sync {
+    /// Return the proxies to use for the URL.
+    fn proxies_for(&self, url: &Url) -> ProviderResult;
+
+    /// Return the name of the provider, under which its proxies are reported in place of
+    /// a variable name, e.g., in errors and in the resolution history.
+    fn name(&self) -> &'static str {
+        "provider"
+    }
+}
+
+impl<T: ProxyProvider + ?Sized> ProxyProvider for Arc<T> {
+    fn proxies_for(&self, url: &Url) -> ProviderResult {
+        (**self).proxies_for(url)
+    }
+
+    fn name(&self) -> &'static str {
+        (**self).name()
+    }
+}
+
+impl<F> ProxyProvider for F where F: Fn(&Url) -> ProviderResult + Send + Sync {
+    fn proxies_for(&self, url: &Url) -> ProviderResult {
+        self(url)
+    }
+}
+
+/// How a provider of a [`ProviderChain`](struct.ProviderChain.html) relates to the sources built
+/// into the resolver.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ProviderMode {
+    /// The provider is consulted only if the built-in sources have no proxy settings applicable
+    /// to the target. This is the default.
+    #[default]
+    FillGaps,
+    /// The provider is consulted before the built-in sources, including the __no_proxy__ check,
+    /// and its answer, if not `Pass`, replaces theirs.
+    Override,
+}
+
+/// An ordered list of proxy providers.
+///
+/// The providers are consulted in the order they were added, or in the order set by
+/// [`with_precedence()`](#method.with_precedence), and the first answer other than
+/// `ProviderResult::Pass` is the answer of the chain. When the chain is plugged into a resolver,
+/// each provider's [`ProviderMode`](enum.ProviderMode.html) determines whether it's consulted
+/// before or after the built-in sources; the resolver reports which provider gave the answer
+/// through [`ProxyUrl::source()`](struct.ProxyUrl.html#method.source) and its history.
+/// A chain is itself a provider, which consults all of its providers regardless of their
+/// modes, so chains can be nested. Cloning a chain is cheap, since the providers are shared.
+#[derive(Clone, Default)]
+pub struct ProviderChain {
+    providers: Vec<(Arc<dyn ProxyProvider>, ProviderMode)>,
+}
+
+impl fmt::Debug for ProviderChain {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list().entries(self.providers.iter().map(|(provider, mode)| (provider.name(), mode))).finish()
+    }
+}
+
+impl ProviderChain {
+    /// Create an empty chain.
+    pub fn new() -> ProviderChain {
+        ProviderChain::default()
+    }
+
+    /// Add a provider which fills gaps to the end of the chain.
+    pub fn with_provider<P: ProxyProvider + 'static>(self, provider: P) -> Self {
+        self.with_provider_mode(provider, ProviderMode::FillGaps)
+    }
+
+    /// Add a provider with the given mode to the end of the chain.
+    pub fn with_provider_mode<P: ProxyProvider + 'static>(mut self, provider: P, mode: ProviderMode) -> Self {
+        self.providers.push((Arc::new(provider), mode));
+        self
+    }
+
+    /// Add a provider with the given mode to the start of the chain.
+    pub(crate) fn with_first_provider_mode<P: ProxyProvider + 'static>(mut self, provider: P, mode: ProviderMode) -> Self {
+        self.providers.insert(0, (Arc::new(provider), mode));
+        self
+    }
+
+    /// Reorder the providers by name.
+    ///
+    /// The providers named in `names` come first, in the order of the names; the others follow
+    /// in their current order. Providers with the same name keep their relative order.
+    pub fn with_precedence<'a, I: IntoIterator<Item = &'a str>>(mut self, names: I) -> Self {
+        let names = names.into_iter().collect::<Vec<_>>();
+        self.providers.sort_by_key(|(provider, _)| {
+            names.iter().position(|&name| name == provider.name()).unwrap_or(names.len())
+        });
+        self
+    }
+
+    /// Return the names of the providers, in the order they are consulted.
+    pub fn names(&self) -> Vec<&'static str> {
+        self.providers.iter().map(|(provider, _)| provider.name()).collect()
+    }
+
+    /// Return the number of providers in the chain.
+    pub fn len(&self) -> usize {
+        self.providers.len()
+    }
+
+    /// Return `true` if the chain has no providers.
+    pub fn is_empty(&self) -> bool {
+        self.providers.is_empty()
+    }
+
+    /// Return the first answer other than `Pass` for the URL from the providers with the mode,
+    /// or from all providers if there's no mode, with the name of the provider which gave it.
+    pub(crate) fn resolve(&self, url: &Url, mode: Option<ProviderMode>) -> Option<(&'static str, ProviderResult)> {
+        self.providers.iter()
+            .filter(|&&(_, provider_mode)| mode.is_none() || mode == Some(provider_mode))
+            .map(|(provider, _)| (provider.name(), provider.proxies_for(url)))
+            .find(|(_, result)| *result != ProviderResult::Pass)
+    }
+
+    /// Return all answers other than `Pass` for the URL from the providers with the mode, in
+    /// the order of the chain.
+    pub(crate) fn resolve_all(&self, url: &Url, mode: ProviderMode) -> Vec<(&'static str, ProviderResult)> {
+        self.providers.iter()
+            .filter(|&&(_, provider_mode)| provider_mode == mode)
+            .map(|(provider, _)| (provider.name(), provider.proxies_for(url)))
+            .filter(|(_, result)| *result != ProviderResult::Pass)
+            .collect()
+    }
+}
+
+impl ProxyProvider for ProviderChain {
+    fn proxies_for(&self, url: &Url) -> ProviderResult {
+        self.resolve(url, None).map(|(_, result)| result).unwrap_or(ProviderResult::Pass)
+    }
+
+    fn name(&self) -> &'static str {
+        "chain"
+    }
+}
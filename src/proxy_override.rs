@@ -0,0 +1,148 @@
+// Copyright (c) 2016 Ivan Nejgebauer <inejge@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::fmt;
+use std::str::FromStr;
+
+use url::Url;
+
+use crate::{matches_no_proxy, parse_proxy_value, redact_password};
+use crate::{Error, NoProxy, ProviderMode, ProviderResult, ProxyProvider, Resolver};
+use crate::{DEFAULT_PROXY_PORT, DEFAULT_PROXY_SCHEME};
+
+/// Proxy settings given on the command line, which take precedence over all other sources.
+///
+/// The type is meant for the `--proxy` and `--noproxy` options of a program, which have the
+/// meaning they have for __curl__. Parsing a `ProxyOverride` from a string, which is what clap's
+/// `value_parser!` does for a `FromStr` type, reads a `--proxy` value: a proxy URL, interpreted
+/// like the value of a proxy variable, or an empty string, which means that no proxy is used.
+/// A `--noproxy` value is parsed into a [`NoProxy`](struct.NoProxy.html) and added with
+/// [`with_no_proxy()`](#method.with_no_proxy).
+///
+/// A `ProxyOverride` is a [`ProxyProvider`](trait.ProxyProvider.html) named `override`, and
+/// [`Resolver::with_override()`](struct.Resolver.html#method.with_override) puts it ahead of all
+/// other sources. A target matching the `--noproxy` list is connected to directly; otherwise,
+/// the `--proxy` value, if given, is used for every target. Without a `--proxy` value, the
+/// other sources are consulted as usual.
+///
+/// The `Debug` output has the proxy password replaced by asterisks.
+///
+/// ```
+/// # extern crate env_proxy;
+/// # extern crate url;
+/// use env_proxy::{NoProxy, ProxyOverride, Resolver};
+///
+/// # fn main() -> Result<(), env_proxy::Error> {
+/// // With clap: #[arg(long)] proxy: Option<ProxyOverride>, #[arg(long)] noproxy: Option<NoProxy>
+/// let proxy: ProxyOverride = "proxy.example.com:3128".parse()?;
+/// let noproxy: NoProxy = "localhost,example.org".parse().unwrap();
+/// let resolver = Resolver::new().with_override(ProxyOverride::from_options(Some(proxy), Some(noproxy)));
+/// let url = url::Url::parse("https://www.example.com/").unwrap();
+/// assert_eq!(resolver.for_url(&url).source(), Some("override"));
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct ProxyOverride {
+    proxy: Option<String>,
+    no_proxy: Option<NoProxy>,
+}
+
+impl fmt::Debug for ProxyOverride {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ProxyOverride")
+            .field("proxy", &self.proxy.as_ref().map(|proxy| redact_password(proxy)))
+            .field("no_proxy", &self.no_proxy)
+            .finish()
+    }
+}
+
+impl FromStr for ProxyOverride {
+    type Err = Error;
+
+    /// Parse a `--proxy` value.
+    ///
+    /// The value is checked as [`ProxyUrl::try_to_url()`](struct.ProxyUrl.html#method.try_to_url)
+    /// would check it, with errors reported for the `--proxy` option, but it's kept as given.
+    fn from_str(value: &str) -> Result<ProxyOverride, Error> {
+        let value = value.trim();
+        if !value.is_empty() {
+            parse_proxy_value("--proxy", value, DEFAULT_PROXY_SCHEME, Some(DEFAULT_PROXY_PORT), false)?;
+        }
+        Ok(ProxyOverride { proxy: Some(value.to_string()), no_proxy: None })
+    }
+}
+
+impl ProxyOverride {
+    /// Create an override which doesn't change anything.
+    pub fn new() -> ProxyOverride {
+        ProxyOverride::default()
+    }
+
+    /// Create an override from the optional values of the `--proxy` and `--noproxy` options.
+    pub fn from_options(proxy: Option<ProxyOverride>, no_proxy: Option<NoProxy>) -> ProxyOverride {
+        let proxy = proxy.unwrap_or_default();
+        match no_proxy {
+            Some(no_proxy) => proxy.with_no_proxy(no_proxy),
+            None => proxy,
+        }
+    }
+
+    /// Connect the targets matching the list directly, replacing a previously set list.
+    pub fn with_no_proxy(self, no_proxy: NoProxy) -> Self {
+        ProxyOverride { no_proxy: Some(no_proxy), ..self }
+    }
+
+    /// Return the `--proxy` value, if given; an empty value means no proxy.
+    pub fn proxy(&self) -> Option<&str> {
+        self.proxy.as_deref()
+    }
+
+    /// Return the `--noproxy` list, if given.
+    pub fn no_proxy(&self) -> Option<&NoProxy> {
+        self.no_proxy.as_ref()
+    }
+
+    /// Return `true` if neither option was given.
+    pub fn is_empty(&self) -> bool {
+        self.proxy.is_none() && self.no_proxy.is_none()
+    }
+}
+
+impl ProxyProvider for ProxyOverride {
+    fn proxies_for(&self, url: &Url) -> ProviderResult {
+        if self.no_proxy.as_ref().is_some_and(|no_proxy| matches_no_proxy(url, no_proxy, None)) {
+            return ProviderResult::Direct;
+        }
+        match self.proxy.as_deref() {
+            Some("") => ProviderResult::Direct,
+            Some(proxy) => ProviderResult::Proxies(vec![proxy.to_string()]),
+            None => ProviderResult::Pass,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        "override"
+    }
+}
+
+impl Resolver {
+    /// Let the command-line proxy settings take precedence over all other sources.
+    ///
+    /// The override is consulted before the resolver's [providers](#method.with_providers), in
+    /// the `Override` mode; an override without any options is not added. Proxies given by the
+    /// override are reported under the name `override`. Since [`with_providers()`](#method.with_providers)
+    /// replaces the chain, the override should be added after the providers.
+    pub fn with_override(mut self, proxy_override: ProxyOverride) -> Self {
+        if proxy_override.is_empty() {
+            return self;
+        }
+        let chain = self.providers.take().unwrap_or_default();
+        self.providers = Some(chain.with_first_provider_mode(proxy_override, ProviderMode::Override));
+        self
+    }
+}
@@ -0,0 +1,80 @@
+// Copyright (c) 2016 Ivan Nejgebauer <inejge@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::convert::TryFrom;
+use std::io;
+use std::net::TcpStream;
+use std::sync::Arc;
+
+use rustls::pki_types::ServerName;
+use rustls::{ClientConfig, ClientConnection, StreamOwned};
+
+use crate::connect::unbracketed;
+use crate::{ProxyTlsConfig, Resolver};
+
+/// The resolver's settings for TLS connections to HTTPS proxies.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct ProxyTlsOptions {
+    config: Option<Arc<ClientConfig>>,
+    server_name: Option<String>,
+}
+
+impl Resolver {
+    /// Use the __rustls__ configuration for TLS connections to HTTPS proxies (requires the
+    /// `rustls` feature.)
+    ///
+    /// The configuration decides how the proxy's certificate is verified, and which client
+    /// certificate, if any, is presented to it. Without it, the configuration is built for each
+    /// connection by [`ProxyTlsConfig::client_config()`](struct.ProxyTlsConfig.html#method.client_config)
+    /// from the variables read by [`ProxyTlsConfig::from_env()`](struct.ProxyTlsConfig.html#method.from_env).
+    pub fn with_proxy_tls(mut self, config: Arc<ClientConfig>) -> Self {
+        self.proxy_tls.config = Some(config);
+        self
+    }
+
+    /// Send the name in the TLS Server Name Indication extension to HTTPS proxies, and verify
+    /// their certificates for it (requires the `rustls` feature.)
+    ///
+    /// By default, the host of the proxy URL is used, which is the right choice unless the proxy
+    /// is reached by an address which doesn't appear in its certificate.
+    pub fn with_proxy_server_name(mut self, name: &str) -> Self {
+        self.proxy_tls.server_name = Some(name.to_string());
+        self
+    }
+
+    /// Return the client configuration and the server name for the HTTPS proxy host.
+    pub(crate) fn proxy_tls_params(&self, proxy_host: &str) -> io::Result<(Arc<ClientConfig>, ServerName<'static>)> {
+        let config = match &self.proxy_tls.config {
+            Some(config) => config.clone(),
+            None => Arc::new(ProxyTlsConfig::from_env().client_config()?),
+        };
+        let name = self.proxy_tls.server_name.as_deref().unwrap_or_else(|| unbracketed(proxy_host));
+        let name = ServerName::try_from(name.to_string())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, format!("invalid proxy server name '{}': {}", name, e)))?;
+        Ok((config, name))
+    }
+
+    /// Complete the TLS handshake with the HTTPS proxy over the connection.
+    pub(crate) fn proxy_tls_handshake(&self, stream: TcpStream, proxy_host: &str) -> io::Result<StreamOwned<ClientConnection, TcpStream>> {
+        let (config, name) = self.proxy_tls_params(proxy_host)?;
+        let connection = ClientConnection::new(config, name).map_err(io::Error::other)?;
+        let mut stream = StreamOwned::new(connection, stream);
+        while stream.conn.is_handshaking() {
+            stream.conn.complete_io(&mut stream.sock)?;
+        }
+        Ok(stream)
+    }
+
+    /// Complete the TLS handshake with the HTTPS proxy over the __tokio__ connection.
+    #[cfg(feature = "tokio-rustls")]
+    pub(crate) async fn proxy_tls_handshake_tokio(&self, stream: tokio::net::TcpStream, proxy_host: &str)
+        -> io::Result<tokio_rustls::client::TlsStream<tokio::net::TcpStream>>
+    {
+        let (config, name) = self.proxy_tls_params(proxy_host)?;
+        tokio_rustls::TlsConnector::from(config).connect(name, stream).await
+    }
+}
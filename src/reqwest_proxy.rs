@@ -0,0 +1,106 @@
+// Copyright (c) 2016 Ivan Nejgebauer <inejge@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::convert::TryFrom;
+
+use crate::trace::warn;
+use crate::{default_resolver, Error, ProxyUrl, Resolver};
+
+impl TryFrom<ProxyUrl> for reqwest::Proxy {
+    type Error = Error;
+
+    /// Convert the proxy into a `reqwest::Proxy` for all requests (requires the `reqwest` feature.)
+    ///
+    /// The URL is obtained as with [`try_to_url()`](struct.ProxyUrl.html#method.try_to_url), and
+    /// its credentials become the proxy's basic authorization. A wrapped `None` value is an error,
+    /// as is a scheme which reqwest doesn't support, e.g., a SOCKS scheme without reqwest's `socks`
+    /// feature.
+    fn try_from(proxy: ProxyUrl) -> Result<reqwest::Proxy, Error> {
+        let var = proxy.var;
+        let url = proxy.try_to_url()?.ok_or(Error::NoValue)?;
+        let scheme = url.scheme().to_string();
+        reqwest::Proxy::all(url).map_err(|_| Error::UnsupportedScheme { var: var.to_string(), scheme })
+    }
+}
+
+impl Resolver {
+    /// Return the proxies to register on a `reqwest::ClientBuilder` (requires the `reqwest` feature.)
+    ///
+    /// The proxies consult the resolver for each request, so that all of its sources, including
+    /// the __no_proxy__ list with its full syntax, are applied exactly as by
+    /// [`for_url()`](#method.for_url), which reqwest's own environment handling doesn't do. A proxy
+    /// value which can't be used is logged, and the request is sent directly. SOCKS proxies are
+    /// used only with the `reqwest-socks` feature, which enables reqwest's `socks` feature;
+    /// without it, they are logged and skipped.
+    ///
+    /// Currently, this is a single custom proxy; register all returned proxies, since later
+    /// versions may split them, e.g., by scheme.
+    pub fn reqwest_proxies(&self) -> Vec<reqwest::Proxy> {
+        let resolver = self.clone();
+        let custom = reqwest::Proxy::custom(move |url| match resolver.for_url(url).try_to_url() {
+            Ok(Some(proxy)) if proxy.scheme().starts_with("socks") && !cfg!(feature = "reqwest-socks") => {
+                warn!("SOCKS proxies require the reqwest-socks feature; connecting directly");
+                None
+            },
+            Ok(proxy) => proxy,
+            Err(e) => {
+                warn!("{}", e);
+                None
+            },
+        });
+        vec![custom]
+    }
+}
+
+/// Return the proxies to register on a `reqwest::ClientBuilder` (requires the `reqwest` feature.)
+///
+/// The proxies use the resolver of [`for_url()`](fn.for_url.html), as described for
+/// [`Resolver::reqwest_proxies()`](struct.Resolver.html#method.reqwest_proxies).
+///
+/// ```no_run
+/// # extern crate env_proxy;
+/// # extern crate reqwest;
+/// # fn main() {
+/// let client = env_proxy::reqwest_proxies().into_iter()
+///     .fold(reqwest::Client::builder().no_proxy(), |builder, proxy| builder.proxy(proxy))
+///     .build();
+/// # }
+/// ```
+pub fn reqwest_proxies() -> Vec<reqwest::Proxy> {
+    default_resolver().reqwest_proxies()
+}
+
+/// An extension trait for installing the proxies of this crate on a reqwest client builder
+/// (requires the `reqwest` feature.)
+///
+/// ```no_run
+/// # extern crate env_proxy;
+/// # extern crate reqwest;
+/// # fn main() {
+/// use env_proxy::ApplyEnvProxy;
+///
+/// let client = reqwest::Client::builder().with_env_proxy().build();
+/// # }
+/// ```
+pub trait ApplyEnvProxy: Sized {
+    /// Replace the builder's proxies with those of [`reqwest_proxies()`](fn.reqwest_proxies.html).
+    fn with_env_proxy(self) -> Self {
+        self.with_resolver_proxy(&default_resolver())
+    }
+
+    /// Replace the builder's proxies with those of
+    /// [`Resolver::reqwest_proxies()`](struct.Resolver.html#method.reqwest_proxies).
+    fn with_resolver_proxy(self, resolver: &Resolver) -> Self;
+}
+
+impl ApplyEnvProxy for reqwest::ClientBuilder {
+    /// Disable reqwest's own proxy detection and register the resolver's proxies, whose
+    /// lookups also decide which requests bypass the proxy.
+    fn with_resolver_proxy(self, resolver: &Resolver) -> Self {
+        resolver.reqwest_proxies().into_iter().fold(self.no_proxy(), |builder, proxy| builder.proxy(proxy))
+    }
+}
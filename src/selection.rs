@@ -0,0 +1,275 @@
+// Copyright (c) 2016 Ivan Nejgebauer <inejge@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use url::Url;
+
+use crate::target::Target;
+use crate::trace::{debug, warn};
+use crate::{ProxyUrl, Resolver};
+
+/// The way a resolver chooses among several candidate proxies for a target.
+///
+/// The candidates are the proxies in order of precedence, as for
+/// [`Resolver::for_url_all()`](struct.Resolver.html#method.for_url_all): those listed in the
+/// applicable variable, followed by __all_proxy__, or those given by a provider or a PAC script.
+/// The chosen proxy is returned by [`for_url()`](struct.Resolver.html#method.for_url), and the
+/// others follow it in their original order, wrapping around, for a resolver which
+/// [falls back](struct.Resolver.html#method.with_fallback_on_error) on errors. A choice made by a
+/// strategy other than `First` is recorded in the resolver's history as
+/// [`Outcome::Selected`](enum.Outcome.html#variant.Selected), and logged at the debug level.
+///
+/// Candidates [reported as failing](struct.Resolver.html#method.report_failure) are left out of
+/// the choice while their cooldown lasts, and put after the others, unless all of them are
+/// failing. A choice other than the first candidate is then recorded as `Selected` for any
+/// strategy, including `First`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Selection {
+    /// Choose the first candidate, in order of precedence. This is the default.
+    #[default]
+    First,
+    /// Choose the first candidate accepted by the resolver's
+    /// [health check](struct.Resolver.html#method.with_health_check), or the first candidate if
+    /// none is.
+    FirstHealthy,
+    /// Choose the candidates in turn, one per resolution; the turn is shared by all targets
+    /// and by the clones of the resolver.
+    RoundRobin,
+    /// Choose a candidate at random, with the probability proportional to its
+    /// [weight](struct.Resolver.html#method.with_proxy_weights).
+    WeightedRandom,
+}
+
+/// The health check of a resolver.
+#[derive(Clone)]
+pub(crate) struct HealthCheck(Arc<dyn Fn(&Url) -> bool + Send + Sync>);
+
+impl fmt::Debug for HealthCheck {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("HealthCheck")
+    }
+}
+
+/// The default time for which a failing proxy is demoted.
+pub(crate) const DEFAULT_FAILURE_COOLDOWN: Duration = Duration::from_secs(30);
+
+/// The proxy endpoints reported as failing, with the end of their cooldown.
+pub(crate) type Failures = Mutex<HashMap<String, Instant>>;
+
+/// Return the key of the proxy endpoint in the failure memory.
+fn endpoint_key(proxy: &Url) -> Option<String> {
+    let host = proxy.host_str()?.to_ascii_lowercase();
+    Some(format!("{}:{}", host, proxy.port_or_known_default().map(|p| p.to_string()).unwrap_or_default()))
+}
+
+/// Return a random number below `n`, which must not be zero.
+///
+/// The randomness of the standard library's hash keys is good enough for spreading the load.
+fn random_below(n: u64) -> u64 {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u64(COUNTER.fetch_add(1, Ordering::Relaxed));
+    hasher.finish() % n
+}
+
+impl Resolver {
+    /// Choose among several candidate proxies with the strategy.
+    ///
+    /// See [`Selection`](enum.Selection.html).
+    pub fn with_selection(mut self, selection: Selection) -> Self {
+        self.selection = selection;
+        self
+    }
+
+    /// Check the health of candidate proxies with the function, for the `FirstHealthy`
+    /// [selection](enum.Selection.html).
+    ///
+    /// The function is given the proxy URL, as transformed by
+    /// [`ProxyUrl::to_url()`](struct.ProxyUrl.html#method.to_url), and should return quickly,
+    /// e.g., by consulting the results of periodic probes; a candidate which can't be transformed
+    /// is considered unhealthy. Without a health check, all candidates are healthy.
+    pub fn with_health_check<F>(mut self, check: F) -> Self
+        where F: Fn(&Url) -> bool + Send + Sync + 'static
+    {
+        self.health_check = Some(HealthCheck(Arc::new(check)));
+        self
+    }
+
+    /// Set the weights of proxy endpoints for the `WeightedRandom`
+    /// [selection](enum.Selection.html).
+    ///
+    /// Each endpoint is a host name or address, which matches the proxy on any port, or a
+    /// __host:port__ pair, as for [`with_denied_proxies()`](#method.with_denied_proxies). A proxy
+    /// matching no endpoint has the weight 1, and one with the weight 0 is chosen only if all
+    /// candidates have it. Entries which can't be parsed are logged and skipped. Repeated calls
+    /// add to the list; the first matching entry applies.
+    pub fn with_proxy_weights<I, S>(mut self, weights: I) -> Self
+        where I: IntoIterator<Item = (S, u32)>, S: AsRef<str>
+    {
+        for (endpoint, weight) in weights {
+            let endpoint = endpoint.as_ref();
+            match Url::parse(&format!("xttp://{}", endpoint)) {
+                Ok(ref url) if url.host_str().is_some() && url.path().is_empty() =>
+                    self.weights.push((url.host_str().unwrap().to_ascii_lowercase(), url.port(), weight)),
+                _ => warn!("invalid weighted proxy endpoint '{}'", endpoint),
+            }
+        }
+        self
+    }
+
+    fn proxy_weight(&self, proxy: Option<&Url>) -> u64 {
+        let proxy = match proxy {
+            Some(proxy) => proxy,
+            None => return 0,
+        };
+        let host = proxy.host_str().unwrap_or("").to_ascii_lowercase();
+        let port = proxy.port_or_known_default();
+        self.weights.iter()
+            .find(|&&(ref w_host, w_port, _)| *w_host == host && (w_port.is_none() || w_port == port))
+            .map(|&(_, _, weight)| u64::from(weight))
+            .unwrap_or(1)
+    }
+
+    /// Demote the proxy for the resolver's [cooldown](#method.with_failure_cooldown) after
+    /// it has failed, so that the following resolutions prefer the other candidates.
+    ///
+    /// The proxy is identified by its host and port, as given by
+    /// [`ProxyUrl::to_url()`](struct.ProxyUrl.html#method.to_url), so that the report applies to
+    /// the endpoint regardless of the variable or provider which named it. Reporting a proxy
+    /// again restarts its cooldown. A value which can't be transformed into a URL is ignored.
+    /// Clones of the resolver share the reports.
+    ///
+    /// ```
+    /// # extern crate env_proxy;
+    /// # extern crate url;
+    /// use env_proxy::Resolver;
+    ///
+    /// # fn main() {
+    /// let resolver = Resolver::new()
+    ///     .with_vars(vec![("https_proxy", "p1.example.com:3128,p2.example.com:3128")]);
+    /// let url = url::Url::parse("https://www.example.com/").unwrap();
+    /// let proxy = resolver.for_url(&url);
+    /// // ... connecting through the proxy fails
+    /// resolver.report_failure(&proxy);
+    /// assert_eq!(resolver.for_url(&url).host_port(), Some(("p2.example.com".to_string(), 3128)));
+    /// # }
+    /// ```
+    pub fn report_failure(&self, proxy: &ProxyUrl) {
+        let key = match proxy.resolved_url().ok().flatten().as_deref().and_then(endpoint_key) {
+            Some(key) => key,
+            None => return,
+        };
+        debug!("demoting proxy {} for {:?}", key, self.cooldown);
+        let mut failures = self.failures.lock().unwrap_or_else(|e| e.into_inner());
+        let now = Instant::now();
+        failures.retain(|_, until| *until > now);
+        failures.insert(key, now + self.cooldown);
+    }
+
+    /// Lift the demotion of the proxy, if it was [reported as failing](#method.report_failure).
+    pub fn report_success(&self, proxy: &ProxyUrl) {
+        if let Some(key) = proxy.resolved_url().ok().flatten().as_deref().and_then(endpoint_key) {
+            self.failures.lock().unwrap_or_else(|e| e.into_inner()).remove(&key);
+        }
+    }
+
+    /// Set the time for which a proxy [reported as failing](#method.report_failure) is demoted.
+    ///
+    /// The default is 30 seconds. The cooldown applies to the reports made after the call.
+    pub fn with_failure_cooldown(mut self, cooldown: Duration) -> Self {
+        self.cooldown = cooldown;
+        self
+    }
+
+    /// Return, for each candidate URL, whether it's demoted after a failure.
+    fn demoted(&self, urls: &[Option<&Url>]) -> Vec<bool> {
+        let failures = self.failures.lock().unwrap_or_else(|e| e.into_inner());
+        if failures.is_empty() {
+            return vec![false; urls.len()];
+        }
+        let now = Instant::now();
+        urls.iter()
+            .map(|url| url
+                .and_then(endpoint_key)
+                .and_then(|key| failures.get(&key))
+                .is_some_and(|until| *until > now))
+            .collect()
+    }
+
+    /// Return the position of the candidate chosen by the resolver's strategy among the
+    /// candidate URLs, or `None` if the strategy doesn't apply.
+    fn choose(&self, urls: &[Option<&Url>]) -> Option<usize> {
+        if self.selection == Selection::First || urls.len() < 2 {
+            return None;
+        }
+        let chosen = match self.selection {
+            Selection::FirstHealthy => urls.iter()
+                .position(|url| match (url, &self.health_check) {
+                    (Some(url), Some(check)) => (check.0)(url),
+                    (Some(_), None) => true,
+                    (None, _) => false,
+                })
+                .unwrap_or(0),
+            Selection::RoundRobin => (self.rotation.fetch_add(1, Ordering::Relaxed) % urls.len() as u64) as usize,
+            Selection::WeightedRandom => {
+                let weights = urls.iter().map(|url| self.proxy_weight(*url)).collect::<Vec<_>>();
+                let total = weights.iter().sum::<u64>();
+                if total == 0 {
+                    random_below(urls.len() as u64) as usize
+                } else {
+                    let mut point = random_below(total);
+                    weights.iter().position(|&weight| {
+                        let hit = point < weight;
+                        point = point.saturating_sub(weight);
+                        hit
+                    }).unwrap_or(0)
+                }
+            },
+            Selection::First => 0,
+        };
+        Some(chosen)
+    }
+
+    /// Return the candidate proxy values for the target, starting with the one chosen by the
+    /// resolver's strategy among those which aren't demoted, their positions in order of
+    /// precedence, and whether the choice should be recorded as a selection. The positions are
+    /// empty if the order is unchanged.
+    pub(crate) fn selected_candidates(&self, target: &Target, candidates: Vec<ProxyUrl>) -> (Vec<ProxyUrl>, Vec<usize>, bool) {
+        let no_failures = || self.failures.lock().unwrap_or_else(|e| e.into_inner()).is_empty();
+        if candidates.len() < 2 || (self.selection == Selection::First && no_failures()) {
+            return (candidates, Vec::new(), false);
+        }
+        let candidates = candidates.into_iter().map(ProxyUrl::parse).collect::<Vec<_>>();
+        let urls = candidates.iter().map(|proxy| proxy.resolved_url().ok().flatten()).collect::<Vec<_>>();
+        let urls = urls.iter().map(Option::as_deref).collect::<Vec<_>>();
+        let demoted = self.demoted(&urls);
+        let all_demoted = demoted.iter().all(|&demoted| demoted);
+        let mut order = (0..candidates.len()).filter(|&i| all_demoted || !demoted[i]).collect::<Vec<_>>();
+        let chosen = self.choose(&order.iter().map(|&i| urls[i]).collect::<Vec<_>>());
+        if let Some(chosen) = chosen {
+            order.rotate_left(chosen);
+        }
+        if !all_demoted {
+            order.extend((0..candidates.len()).filter(|&i| demoted[i]));
+        }
+        let selected = chosen.is_some() || order.first().is_some_and(|&first| first != 0);
+        if selected {
+            debug!("{:?} selection chose candidate {} of {} for {}", self.selection, order[0] + 1, candidates.len(), target);
+        }
+        let mut candidates = candidates.into_iter().map(Some).collect::<Vec<_>>();
+        let candidates = order.iter().filter_map(|&i| candidates[i].take()).collect();
+        (candidates, order, selected)
+    }
+}
@@ -0,0 +1,89 @@
+// Copyright (c) 2016 Ivan Nejgebauer <inejge@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use percent_encoding::percent_decode_str;
+use url::Url;
+
+use crate::trace::warn;
+use crate::{default_resolver, ProxyKind, Resolver};
+
+/// Quote a word for the shell which runs the `ProxyCommand`, and escape the `%` which would
+/// start an OpenSSH token.
+fn command_word(word: &str) -> String {
+    let plain = !word.is_empty() && word.chars().all(|c| c.is_ascii_alphanumeric() || "-._:@[]/=+,".contains(c));
+    let word = if plain { word.to_string() } else { format!("'{}'", word.replace('\'', "'\\''")) };
+    word.replace('%', "%%")
+}
+
+impl Resolver {
+    /// Return an OpenSSH `ProxyCommand` which reaches an `ssh` target through the proxy
+    /// determined for it.
+    ///
+    /// The target is an `ssh://[user@]host[:port]` URL, for which the proxy is determined by
+    /// [`for_url()`](#method.for_url), so that only __all_proxy__ is checked. The command uses
+    /// OpenBSD __netcat__: `nc -X connect` for an HTTP proxy, with the proxy user name passed by
+    /// `-P`, and `nc -X 4` or `nc -X 5` for a SOCKS proxy; the host and port are left to ssh, as
+    /// `%h` and `%p`. The command can be given to ssh with `-o ProxyCommand=...`, or written to
+    /// an `ssh_config` file.
+    ///
+    /// If the target should be connected to directly, or isn't an `ssh` URL, `None` is returned.
+    /// Since netcat can't use them, an HTTPS proxy and SOCKS credentials are logged and give
+    /// `None`; a proxy password is left for netcat to prompt for.
+    pub fn ssh_proxy_command(&self, target: &str) -> Option<String> {
+        let target = match Url::parse(target) {
+            Ok(target) if target.scheme() == "ssh" && target.host_str().is_some() => target,
+            _ => {
+                warn!("'{}' is not an ssh:// URL", target);
+                return None;
+            },
+        };
+        let proxy = self.for_url(&target).to_url()?;
+        let address = format!("{}:{}", proxy.host_str()?, proxy.port_or_known_default()?);
+        let user = percent_decode_str(proxy.username()).decode_utf8_lossy();
+        let protocol = match ProxyKind::from_scheme(proxy.scheme()) {
+            Some(ProxyKind::Http) => "connect",
+            Some(_) if !user.is_empty() => {
+                warn!("netcat can't authenticate to the SOCKS proxy '{}'", address);
+                return None;
+            },
+            Some(ProxyKind::Socks4) | Some(ProxyKind::Socks4a) => "4",
+            Some(ProxyKind::Socks5) | Some(ProxyKind::Socks5h) => "5",
+            _ => {
+                warn!("proxy URL scheme '{}' is not supported by netcat", proxy.scheme());
+                return None;
+            },
+        };
+        let mut command = format!("nc -X {} -x {}", protocol, command_word(&address));
+        if !user.is_empty() {
+            command.push_str(" -P ");
+            command.push_str(&command_word(&user));
+        }
+        command.push_str(" %h %p");
+        Some(command)
+    }
+}
+
+/// Return an OpenSSH `ProxyCommand` which reaches an `ssh` target through the proxy from the
+/// environment.
+///
+/// The proxy is determined with the resolver of [`for_url()`](fn.for_url.html), as described
+/// for [`Resolver::ssh_proxy_command()`](struct.Resolver.html#method.ssh_proxy_command).
+///
+/// ```no_run
+/// # extern crate env_proxy;
+/// # fn main() -> std::io::Result<()> {
+/// let mut ssh = std::process::Command::new("ssh");
+/// if let Some(command) = env_proxy::ssh_proxy_command("ssh://git@git.example.com") {
+///     ssh.arg("-o").arg(format!("ProxyCommand={}", command));
+/// }
+/// ssh.arg("git@git.example.com").status()?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn ssh_proxy_command(target: &str) -> Option<String> {
+    default_resolver().ssh_proxy_command(target)
+}
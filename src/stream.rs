@@ -0,0 +1,194 @@
+// Copyright (c) 2016 Ivan Nejgebauer <inejge@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::io;
+#[cfg(feature = "connect")]
+use std::io::{Read, Write};
+#[cfg(feature = "tokio")]
+use std::pin::Pin;
+#[cfg(feature = "tokio")]
+use std::task::{Context, Poll};
+
+#[cfg(feature = "connect")]
+#[derive(Debug)]
+enum Inner {
+    Tcp(std::net::TcpStream),
+    #[cfg(feature = "rustls")]
+    Tls(Box<rustls::StreamOwned<rustls::ClientConnection, std::net::TcpStream>>),
+}
+
+/// A connection opened through a proxy (requires the `connect` feature.)
+///
+/// The stream carries the connection to the target. It's a plain TCP connection, unless the
+/// proxy is an HTTPS proxy, in which case the tunnel runs over TLS to the proxy; the target
+/// connection is still only a byte stream, to which the caller adds TLS if the target needs it.
+#[cfg(feature = "connect")]
+#[derive(Debug)]
+pub struct ProxyStream {
+    inner: Inner,
+}
+
+#[cfg(feature = "connect")]
+impl ProxyStream {
+    pub(crate) fn tcp(stream: std::net::TcpStream) -> ProxyStream {
+        ProxyStream { inner: Inner::Tcp(stream) }
+    }
+
+    #[cfg(feature = "rustls")]
+    pub(crate) fn tls(stream: rustls::StreamOwned<rustls::ClientConnection, std::net::TcpStream>) -> ProxyStream {
+        ProxyStream { inner: Inner::Tls(Box::new(stream)) }
+    }
+
+    /// Return the underlying TCP connection, to the proxy or to the target.
+    ///
+    /// The connection can be used for socket options and addresses; reading or writing it
+    /// directly would corrupt a TLS connection to the proxy.
+    pub fn tcp_stream(&self) -> &std::net::TcpStream {
+        match &self.inner {
+            Inner::Tcp(stream) => stream,
+            #[cfg(feature = "rustls")]
+            Inner::Tls(stream) => stream.get_ref(),
+        }
+    }
+
+    /// Return `true` if the connection to the proxy uses TLS.
+    pub fn is_proxy_tls(&self) -> bool {
+        !matches!(self.inner, Inner::Tcp(_))
+    }
+
+    /// Return the TCP connection, or the stream itself if it runs over TLS to the proxy.
+    pub fn into_tcp_stream(self) -> Result<std::net::TcpStream, ProxyStream> {
+        match self.inner {
+            Inner::Tcp(stream) => Ok(stream),
+            #[cfg(feature = "rustls")]
+            inner => Err(ProxyStream { inner }),
+        }
+    }
+}
+
+#[cfg(feature = "connect")]
+impl Read for ProxyStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match &mut self.inner {
+            Inner::Tcp(stream) => stream.read(buf),
+            #[cfg(feature = "rustls")]
+            Inner::Tls(stream) => stream.read(buf),
+        }
+    }
+}
+
+#[cfg(feature = "connect")]
+impl Write for ProxyStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match &mut self.inner {
+            Inner::Tcp(stream) => stream.write(buf),
+            #[cfg(feature = "rustls")]
+            Inner::Tls(stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.inner {
+            Inner::Tcp(stream) => stream.flush(),
+            #[cfg(feature = "rustls")]
+            Inner::Tls(stream) => stream.flush(),
+        }
+    }
+}
+
+#[cfg(feature = "tokio")]
+#[derive(Debug)]
+enum AsyncInner {
+    Tcp(tokio::net::TcpStream),
+    #[cfg(feature = "tokio-rustls")]
+    Tls(Box<tokio_rustls::client::TlsStream<tokio::net::TcpStream>>),
+}
+
+/// A __tokio__ connection opened through a proxy (requires the `tokio` feature.)
+///
+/// The asynchronous counterpart of [`ProxyStream`](struct.ProxyStream.html); TLS to an HTTPS
+/// proxy requires the `tokio-rustls` feature.
+#[cfg(feature = "tokio")]
+#[derive(Debug)]
+pub struct AsyncProxyStream {
+    inner: AsyncInner,
+}
+
+#[cfg(feature = "tokio")]
+impl AsyncProxyStream {
+    pub(crate) fn tcp(stream: tokio::net::TcpStream) -> AsyncProxyStream {
+        AsyncProxyStream { inner: AsyncInner::Tcp(stream) }
+    }
+
+    #[cfg(feature = "tokio-rustls")]
+    pub(crate) fn tls(stream: tokio_rustls::client::TlsStream<tokio::net::TcpStream>) -> AsyncProxyStream {
+        AsyncProxyStream { inner: AsyncInner::Tls(Box::new(stream)) }
+    }
+
+    /// Return the underlying TCP connection, to the proxy or to the target.
+    ///
+    /// See [`ProxyStream::tcp_stream()`](struct.ProxyStream.html#method.tcp_stream).
+    pub fn tcp_stream(&self) -> &tokio::net::TcpStream {
+        match &self.inner {
+            AsyncInner::Tcp(stream) => stream,
+            #[cfg(feature = "tokio-rustls")]
+            AsyncInner::Tls(stream) => stream.get_ref().0,
+        }
+    }
+
+    /// Return `true` if the connection to the proxy uses TLS.
+    pub fn is_proxy_tls(&self) -> bool {
+        !matches!(self.inner, AsyncInner::Tcp(_))
+    }
+
+    /// Return the TCP connection, or the stream itself if it runs over TLS to the proxy.
+    pub fn into_tcp_stream(self) -> Result<tokio::net::TcpStream, AsyncProxyStream> {
+        match self.inner {
+            AsyncInner::Tcp(stream) => Ok(stream),
+            #[cfg(feature = "tokio-rustls")]
+            inner => Err(AsyncProxyStream { inner }),
+        }
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl tokio::io::AsyncRead for AsyncProxyStream {
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut tokio::io::ReadBuf<'_>) -> Poll<io::Result<()>> {
+        match &mut self.inner {
+            AsyncInner::Tcp(stream) => Pin::new(stream).poll_read(cx, buf),
+            #[cfg(feature = "tokio-rustls")]
+            AsyncInner::Tls(stream) => Pin::new(stream.as_mut()).poll_read(cx, buf),
+        }
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl tokio::io::AsyncWrite for AsyncProxyStream {
+    fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        match &mut self.inner {
+            AsyncInner::Tcp(stream) => Pin::new(stream).poll_write(cx, buf),
+            #[cfg(feature = "tokio-rustls")]
+            AsyncInner::Tls(stream) => Pin::new(stream.as_mut()).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match &mut self.inner {
+            AsyncInner::Tcp(stream) => Pin::new(stream).poll_flush(cx),
+            #[cfg(feature = "tokio-rustls")]
+            AsyncInner::Tls(stream) => Pin::new(stream.as_mut()).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match &mut self.inner {
+            AsyncInner::Tcp(stream) => Pin::new(stream).poll_shutdown(cx),
+            #[cfg(feature = "tokio-rustls")]
+            AsyncInner::Tls(stream) => Pin::new(stream.as_mut()).poll_shutdown(cx),
+        }
+    }
+}
@@ -0,0 +1,144 @@
+// Copyright (c) 2016 Ivan Nejgebauer <inejge@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::borrow::Cow;
+use std::cell::OnceCell;
+use std::fmt;
+
+use url::Url;
+
+use crate::config::{fnv1a, FNV_OFFSET};
+use crate::trace::warn;
+
+/// The target of a resolution, given either as a URL or as its scheme, host and port.
+///
+/// Most of a resolution needs only the parts; a URL is built from them when a provider, a PAC
+/// script or PacRunner asks for one.
+pub(crate) struct Target<'a> {
+    scheme: Cow<'a, str>,
+    host: Option<Cow<'a, str>>,
+    port: Option<u16>,
+    url: Option<&'a Url>,
+    built: OnceCell<Option<Url>>,
+}
+
+/// Return the default port of a scheme with a tuple origin.
+fn special_port(scheme: &str) -> Option<u16> {
+    match scheme {
+        "http" | "ws" => Some(80),
+        "https" | "wss" => Some(443),
+        "ftp" => Some(21),
+        _ => None,
+    }
+}
+
+impl<'a> Target<'a> {
+    pub(crate) fn from_url(url: &'a Url) -> Target<'a> {
+        Target {
+            scheme: Cow::Borrowed(url.scheme()),
+            host: url.host_str().map(Cow::Borrowed),
+            port: url.port_or_known_default(),
+            url: Some(url),
+            built: OnceCell::new(),
+        }
+    }
+
+    /// Create a target from its parts, writing the host as it appears in a URL: in lowercase,
+    /// with an IPv6 address in brackets.
+    pub(crate) fn from_parts(scheme: &'a str, host: &'a str, port: u16) -> Target<'a> {
+        let lowercase = |s: &'a str| if s.bytes().any(|b| b.is_ascii_uppercase()) {
+            Cow::Owned(s.to_ascii_lowercase())
+        } else {
+            Cow::Borrowed(s)
+        };
+        let host = if host.contains(':') && !host.starts_with('[') {
+            Cow::Owned(format!("[{}]", host.to_ascii_lowercase()))
+        } else {
+            lowercase(host)
+        };
+        Target {
+            scheme: lowercase(scheme),
+            host: Some(host).filter(|host| !host.is_empty()),
+            port: Some(port),
+            url: None,
+            built: OnceCell::new(),
+        }
+    }
+
+    pub(crate) fn scheme(&self) -> &str {
+        &self.scheme
+    }
+
+    pub(crate) fn host_str(&self) -> Option<&str> {
+        self.host.as_deref()
+    }
+
+    pub(crate) fn port_or_known_default(&self) -> Option<u16> {
+        self.port
+    }
+
+    /// Return the URL of the target, building it from the parts if necessary; a failure to
+    /// build it is logged.
+    pub(crate) fn url(&self) -> Option<&Url> {
+        if let Some(url) = self.url {
+            return Some(url);
+        }
+        self.built.get_or_init(|| {
+            let url = format!("{}/", self);
+            Url::parse(&url).map_err(|e| warn!("can't make a URL of '{}': {}", url, e)).ok()
+        }).as_ref()
+    }
+
+    /// Return the hash of the target's origin, as computed by [`target_hash()`](fn.target_hash.html).
+    pub(crate) fn hash(&self) -> u64 {
+        let origin = match self.url {
+            Some(url) => url.origin().ascii_serialization(),
+            None => match special_port(&self.scheme) {
+                Some(_) if self.host.is_none() => "null".to_string(),
+                Some(port) if self.port == Some(port) => format!("{}://{}", self.scheme, self.host_str().unwrap_or("")),
+                Some(_) => self.to_string(),
+                None => "null".to_string(),
+            },
+        };
+        fnv1a(FNV_OFFSET, origin.bytes())
+    }
+}
+
+impl fmt::Display for Target<'_> {
+    /// Format the target as `scheme://host:port`.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}://{}", self.scheme, self.host_str().unwrap_or(""))?;
+        match self.port {
+            Some(port) => write!(f, ":{}", port),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use url::Url;
+
+    use super::Target;
+    use crate::target_hash;
+
+    #[test]
+    fn from_parts() {
+        let target = Target::from_parts("HTTPS", "WWW.Example.com", 443);
+        assert_eq!(target.scheme(), "https");
+        assert_eq!(target.host_str(), Some("www.example.com"));
+        assert_eq!(target.url().map(Url::as_str), Some("https://www.example.com/"));
+        assert_eq!(Target::from_parts("http", "::1", 8080).to_string(), "http://[::1]:8080");
+        for url in ["https://www.example.com", "http://www.example.com:8080", "socks5://localhost:1080"] {
+            let url = Url::parse(url).unwrap();
+            let target = Target::from_parts(url.scheme(), url.host_str().unwrap(), url.port_or_known_default().unwrap());
+            assert_eq!(target.hash(), target_hash(&url));
+            assert_eq!(Target::from_url(&url).hash(), target_hash(&url));
+        }
+        assert!(Target::from_parts("http", "bad host", 80).url().is_none());
+    }
+}
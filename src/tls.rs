@@ -0,0 +1,112 @@
+// Copyright (c) 2016 Ivan Nejgebauer <inejge@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::env::var_os;
+#[cfg(feature = "rustls")]
+use std::io;
+use std::path::{Path, PathBuf};
+#[cfg(feature = "rustls")]
+use std::sync::Arc;
+
+/// The default name of the variable holding the path of the client certificate.
+pub const PROXY_SSL_CERT: &str = "PROXY_SSL_CERT";
+
+/// The default name of the variable holding the path of the client private key.
+pub const PROXY_SSL_KEY: &str = "PROXY_SSL_KEY";
+
+/// Client certificate settings for TLS connections to an HTTPS proxy.
+///
+/// When the connection to the proxy itself uses TLS, the proxy may require the client to
+/// authenticate with a certificate. There is no standard way to specify it in the environment;
+/// this struct reads the paths of the certificate and the private key from __PROXY_SSL_CERT__
+/// and __PROXY_SSL_KEY__ (also accepted in all-lowercase, which takes precedence), or from the
+/// variables chosen by the application. The paths are not checked in any way, and needn't be
+/// valid UTF-8. With the `rustls` feature, the settings can be turned into a client configuration
+/// by [`client_config()`](#method.client_config).
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct ProxyTlsConfig {
+    cert: Option<PathBuf>,
+    key: Option<PathBuf>,
+}
+
+fn path_var(name: &str) -> Option<PathBuf> {
+    var_os(name.to_ascii_lowercase())
+        .or_else(|| var_os(name.to_ascii_uppercase()))
+        .filter(|value| !value.is_empty())
+        .map(PathBuf::from)
+}
+
+impl ProxyTlsConfig {
+    /// Read the settings from the default variables.
+    pub fn from_env() -> ProxyTlsConfig {
+        ProxyTlsConfig::from_vars(PROXY_SSL_CERT, PROXY_SSL_KEY)
+    }
+
+    /// Read the settings from the named variables.
+    ///
+    /// Each variable is looked up in its all-lowercase and all-uppercase variants, in that order.
+    pub fn from_vars(cert_var: &str, key_var: &str) -> ProxyTlsConfig {
+        ProxyTlsConfig {
+            cert: path_var(cert_var),
+            key: path_var(key_var),
+        }
+    }
+
+    /// Return the path of the client certificate.
+    pub fn cert(&self) -> Option<&Path> {
+        self.cert.as_deref()
+    }
+
+    /// Return the path of the client private key.
+    ///
+    /// If only the certificate is defined, the key is customarily expected to be in the same file.
+    pub fn key(&self) -> Option<&Path> {
+        self.key.as_deref()
+    }
+
+    /// Return `true` if neither the certificate nor the key is defined.
+    pub fn is_empty(&self) -> bool {
+        self.cert.is_none() && self.key.is_none()
+    }
+}
+
+/// Report an unusable PEM file.
+#[cfg(feature = "rustls")]
+fn pem_error(path: &Path, e: rustls::pki_types::pem::Error) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("can't read '{}': {}", path.display(), e))
+}
+
+#[cfg(feature = "rustls")]
+impl ProxyTlsConfig {
+    /// Build a __rustls__ client configuration for the connection to an HTTPS proxy (requires
+    /// the `rustls` feature.)
+    ///
+    /// The proxy's certificate is verified against the Mozilla root certificates bundled with
+    /// __webpki-roots__. If the client certificate is defined, the certificate chain is read from
+    /// its file, and the private key from the key file or, if it isn't defined, from the same
+    /// file; both are in PEM format. A key without a certificate is ignored.
+    pub fn client_config(&self) -> io::Result<rustls::ClientConfig> {
+        use rustls::pki_types::pem::PemObject;
+        use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+
+        let roots = rustls::RootCertStore { roots: webpki_roots::TLS_SERVER_ROOTS.to_vec() };
+        let builder = rustls::ClientConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+            .with_safe_default_protocol_versions()
+            .map_err(io::Error::other)?
+            .with_root_certificates(roots);
+        let cert = match self.cert() {
+            Some(cert) => cert,
+            None => return Ok(builder.with_no_client_auth()),
+        };
+        let chain = CertificateDer::pem_file_iter(cert)
+            .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+            .map_err(|e| pem_error(cert, e))?;
+        let key = self.key().unwrap_or(cert);
+        let key = PrivateKeyDer::from_pem_file(key).map_err(|e| pem_error(key, e))?;
+        builder.with_client_auth_cert(chain, key).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))
+    }
+}
@@ -0,0 +1,115 @@
+// Copyright (c) 2016 Ivan Nejgebauer <inejge@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::net::{Ipv4Addr, SocketAddr, TcpStream};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::trace::warn;
+
+/// The variable holding the host of the Tor SOCKS proxy.
+pub const TOR_SOCKS_HOST: &str = "TOR_SOCKS_HOST";
+
+/// The variable holding the port of the Tor SOCKS proxy.
+pub const TOR_SOCKS_PORT: &str = "TOR_SOCKS_PORT";
+
+/// The port on which a Tor daemon listens for SOCKS connections by default.
+pub const TOR_DEFAULT_SOCKS_PORT: u16 = 9050;
+
+/// How long to wait for the default Tor SOCKS port to accept a connection when probing.
+const PROBE_TIMEOUT: Duration = Duration::from_millis(250);
+
+/// How long the outcome of a probe of the default Tor SOCKS port is reused.
+const PROBE_TTL: Duration = Duration::from_secs(60);
+
+/// Whether a [resolver](struct.Resolver.html#method.with_tor_fallback) falls back to the Tor
+/// SOCKS proxy when no proxy variables are defined.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum TorFallback {
+    /// Don't fall back to Tor. This is the default.
+    #[default]
+    Off,
+    /// Use the proxy given by __TOR_SOCKS_HOST__ and __TOR_SOCKS_PORT__, if either is defined.
+    Variables,
+    /// Use the proxy given by the Tor variables, or, if neither is defined, the default Tor
+    /// SOCKS port, `127.0.0.1:9050`, if it accepts connections.
+    Probe,
+}
+
+/// The outcome of the last probe of the default Tor SOCKS port, shared by the clones of a
+/// resolver.
+#[derive(Debug, Default)]
+pub(crate) struct ProbeCache(Mutex<Option<(Instant, bool)>>);
+
+impl ProbeCache {
+    /// Return `true` if the port accepted a connection, probing it again if the last outcome
+    /// is older than `PROBE_TTL`. Concurrent resolutions wait for a single probe.
+    fn listening(&self, addr: &SocketAddr) -> bool {
+        let mut last = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        match *last {
+            Some((probed, listening)) if probed.elapsed() < PROBE_TTL => listening,
+            _ => {
+                let listening = TcpStream::connect_timeout(addr, PROBE_TIMEOUT).is_ok();
+                *last = Some((Instant::now(), listening));
+                listening
+            },
+        }
+    }
+}
+
+/// Determine the Tor SOCKS proxy, returning the name of the variable which indicated it
+/// and the proxy URL.
+///
+/// If either __TOR_SOCKS_HOST__ or __TOR_SOCKS_PORT__ is defined, the proxy is built from
+/// them, using `127.0.0.1` and 9050 for the missing one. Otherwise, with `TorFallback::Probe`,
+/// the proxy is returned if `127.0.0.1:9050` accepts a connection, as last seen by the cache.
+pub(crate) fn detect(fallback: TorFallback, cache: &ProbeCache) -> Option<(&'static str, String)> {
+    let host = env_var_pair!("tor_socks_host", TOR_SOCKS_HOST);
+    let port = env_var_pair!("tor_socks_port", TOR_SOCKS_PORT);
+    let var = match (&host, &port) {
+        (Some((var, _)), _) | (None, Some((var, _))) => *var,
+        (None, None) => {
+            let addr = SocketAddr::from((Ipv4Addr::LOCALHOST, TOR_DEFAULT_SOCKS_PORT));
+            if fallback == TorFallback::Probe && cache.listening(&addr) {
+                return Some(("", format!("socks5h://{}", addr)));
+            }
+            return None;
+        },
+    };
+    let host = host.map(|(_, host)| host).unwrap_or_else(|| Ipv4Addr::LOCALHOST.to_string());
+    let port = match port {
+        Some((var, port)) => match port.trim().parse::<u16>() {
+            Ok(port) => port,
+            Err(_) => {
+                warn!("{}: invalid port", var);
+                return None;
+            },
+        },
+        None => TOR_DEFAULT_SOCKS_PORT,
+    };
+    let host = if host.contains(':') && !host.starts_with('[') { format!("[{}]", host) } else { host };
+    Some((var, format!("socks5h://{}:{}", host, port)))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::TcpListener;
+
+    use super::ProbeCache;
+
+    #[test]
+    fn probe_reused() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let cache = ProbeCache::default();
+        assert!(cache.listening(&addr));
+        drop(listener);
+        assert!(cache.listening(&addr));
+        assert!(!ProbeCache::default().listening(&addr));
+    }
+}
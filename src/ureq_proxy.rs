@@ -0,0 +1,93 @@
+// Copyright (c) 2016 Ivan Nejgebauer <inejge@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::convert::TryFrom;
+
+use percent_encoding::percent_decode_str;
+use ureq::config::ConfigBuilder;
+use ureq::typestate::AgentScope;
+use ureq::{Agent, Proxy, ProxyProtocol};
+use url::Url;
+
+use crate::trace::warn;
+use crate::{default_resolver, Resolver};
+
+/// Translate a proxy URL into a ureq proxy.
+fn ureq_proxy(proxy: &Url) -> Result<Proxy, String> {
+    let protocol = ProxyProtocol::try_from(proxy.scheme())
+        .map_err(|_| format!("proxy URL scheme '{}' is not supported by ureq", proxy.scheme()))?;
+    let mut builder = Proxy::builder(protocol)
+        .host(proxy.host_str().unwrap_or(""))
+        .port(proxy.port_or_known_default().unwrap_or(0));
+    if !proxy.username().is_empty() {
+        builder = builder.username(&percent_decode_str(proxy.username()).decode_utf8_lossy());
+    }
+    if let Some(password) = proxy.password() {
+        builder = builder.password(&percent_decode_str(password).decode_utf8_lossy());
+    }
+    builder.build().map_err(|e| format!("proxy can't be used with ureq: {}", e))
+}
+
+impl Resolver {
+    /// Return the ureq proxy for the URL, or `None` for a direct connection (requires the
+    /// `ureq` feature.)
+    ///
+    /// The proxy URL is determined as by [`for_url()`](#method.for_url) and
+    /// [`ProxyUrl::to_url()`](struct.ProxyUrl.html#method.to_url). Its scheme becomes the ureq
+    /// proxy protocol: `http`, `https`, `socks4`, `socks4a`, `socks5` and `socks5h` are supported.
+    /// Its credentials are decoded and passed to ureq, which sends them as they are. A value which
+    /// can't be used is logged, and `None` is returned.
+    pub fn ureq_proxy_for(&self, url: &Url) -> Option<Proxy> {
+        let proxy = self.for_url(url).to_url()?;
+        match ureq_proxy(&proxy) {
+            Ok(proxy) => Some(proxy),
+            Err(e) => {
+                warn!("{}", e);
+                None
+            },
+        }
+    }
+
+    /// Return a ureq agent configuration builder with the proxy for the URL (requires the
+    /// `ureq` feature.)
+    ///
+    /// The proxy is set as by [`ureq_proxy_for()`](#method.ureq_proxy_for). Without a proxy,
+    /// the builder has none, rather than the one ureq would read from the environment, so that
+    /// the __no_proxy__ rules of this crate apply. Since a ureq agent has a single proxy, the
+    /// agent should be used only for targets resolving to the same proxy.
+    pub fn ureq_config_for(&self, url: &Url) -> ConfigBuilder<AgentScope> {
+        Agent::config_builder().proxy(self.ureq_proxy_for(url))
+    }
+}
+
+/// Return the ureq proxy for the URL, or `None` for a direct connection (requires the `ureq`
+/// feature.)
+///
+/// The proxy is determined with the resolver of [`for_url()`](fn.for_url.html), as described for
+/// [`Resolver::ureq_proxy_for()`](struct.Resolver.html#method.ureq_proxy_for).
+pub fn ureq_proxy_for(url: &Url) -> Option<Proxy> {
+    default_resolver().ureq_proxy_for(url)
+}
+
+/// Return a ureq agent configuration builder with the proxy for the URL (requires the `ureq`
+/// feature.)
+///
+/// See [`Resolver::ureq_config_for()`](struct.Resolver.html#method.ureq_config_for).
+///
+/// ```no_run
+/// # extern crate env_proxy;
+/// # extern crate ureq;
+/// # extern crate url;
+/// # fn main() {
+/// let url = url::Url::parse("https://www.example.com/").unwrap();
+/// let agent = env_proxy::ureq_config_for(&url).build().new_agent();
+/// let response = agent.get(url.as_str()).call();
+/// # }
+/// ```
+pub fn ureq_config_for(url: &Url) -> ConfigBuilder<AgentScope> {
+    default_resolver().ureq_config_for(url)
+}
@@ -0,0 +1,62 @@
+// Copyright (c) 2016 Ivan Nejgebauer <inejge@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::io;
+
+use url::Url;
+
+use crate::connect::url_host_port;
+use crate::{default_resolver, ProxyStream, Resolver};
+
+impl Resolver {
+    /// Open a connection for a WebSocket client handshake with the `ws` or `wss` URL (requires
+    /// the `websocket` feature.)
+    ///
+    /// The proxy is determined as for the `http` or `https` URL with the same host and port, as
+    /// __curl__ does. With an HTTP proxy, a `CONNECT` tunnel to the target is opened, even for
+    /// `ws`, since a forwarding proxy can't be relied on to pass the upgraded connection; with
+    /// a SOCKS proxy, the proxy is asked to connect to the target. HTTPS proxies are reached
+    /// over TLS, as by [`connect()`](#method.connect). Without a proxy, the target is connected
+    /// to directly.
+    ///
+    /// The returned stream is ready for tungstenite's client handshake, preceded by TLS for `wss`.
+    pub fn websocket_stream(&self, url: &Url) -> io::Result<ProxyStream> {
+        let scheme = match url.scheme() {
+            "ws" => "http",
+            "wss" => "https",
+            s => return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("'{}' is not a WebSocket scheme", s))),
+        };
+        let (host, port) = url_host_port(url)?;
+        let mut target = url.clone();
+        target.set_scheme(scheme).map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "can't map the WebSocket URL"))?;
+        target.set_port(Some(port)).unwrap_or(());
+        self.connect_via(&target, host, port)
+    }
+}
+
+/// Open a connection for a WebSocket client handshake with the `ws` or `wss` URL (requires the
+/// `websocket` feature.)
+///
+/// The proxy is determined with the resolver of [`for_url()`](fn.for_url.html), as described
+/// for [`Resolver::websocket_stream()`](struct.Resolver.html#method.websocket_stream).
+///
+/// ```no_run
+/// # extern crate env_proxy;
+/// # extern crate tungstenite;
+/// # extern crate url;
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let url = url::Url::parse("ws://echo.example.com/").unwrap();
+/// let stream = env_proxy::websocket_stream(&url)?;
+/// let (mut socket, _) = tungstenite::client(url.as_str(), stream).map_err(|e| e.to_string())?;
+/// socket.send(tungstenite::Message::text("hello"))?;
+/// println!("{}", socket.read()?);
+/// # Ok(())
+/// # }
+/// ```
+pub fn websocket_stream(url: &Url) -> io::Result<ProxyStream> {
+    default_resolver().websocket_stream(url)
+}
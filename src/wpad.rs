@@ -0,0 +1,210 @@
+// Copyright (c) 2016 Ivan Nejgebauer <inejge@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::fmt;
+use std::fs;
+use std::sync::Arc;
+
+use url::Url;
+
+use crate::dns::DnsHandle;
+use crate::trace::{debug, warn};
+use crate::{DnsResolve, PacScript, SystemDns};
+
+/// The directories where `dhclient` keeps its lease files.
+const LEASE_DIRS: &[&str] = &["/var/lib/dhcp", "/var/lib/dhclient", "/var/lib/NetworkManager"];
+
+/// The smallest number of labels left in the domain name by DNS devolution.
+const MIN_LABELS: usize = 2;
+
+/// Return the value of the last WPAD option (code 252) in the contents of a `dhclient` lease file.
+///
+/// The option is only recorded if `dhclient.conf` requests it, under a name such as `wpad`
+/// (`option wpad code 252 = text;`), or as `option-252` otherwise.
+fn parse_dhclient_leases(contents: &str) -> Option<String> {
+    contents.lines()
+        .rev()
+        .find_map(|line| {
+            let line = line.trim().strip_prefix("option ")?;
+            let (name, value) = line.split_once(char::is_whitespace)?;
+            if name != "wpad" && name != "option-252" {
+                return None;
+            }
+            // Servers commonly terminate the URL with a newline or NUL, which dhclient escapes.
+            let value = value.trim().trim_end_matches(';').trim().trim_matches('"');
+            let value = value.trim_end_matches("\\n").trim_end_matches("\\000");
+            Some(value.to_string()).filter(|v| !v.is_empty())
+        })
+}
+
+/// Return the WPAD host names for the domain, from the most to the least specific, stopping
+/// at a domain with two labels.
+fn devolve(domain: &str) -> Vec<String> {
+    let labels = domain.trim_matches('.').split('.').filter(|l| !l.is_empty()).collect::<Vec<_>>();
+    (0..labels.len())
+        .take_while(|&i| labels.len() - i >= MIN_LABELS)
+        .map(|i| format!("wpad.{}", labels[i..].join(".").to_ascii_lowercase()))
+        .collect()
+}
+
+/// Return the DNS domain of the host, if it can be determined.
+fn local_domain() -> Option<String> {
+    #[cfg(unix)]
+    {
+        let resolv = fs::read_to_string("/etc/resolv.conf").ok()?;
+        let mut search = None;
+        for line in resolv.lines() {
+            let mut words = line.split_whitespace();
+            match words.next() {
+                Some("domain") => return words.next().map(str::to_string),
+                Some("search") if search.is_none() => search = words.next().map(str::to_string),
+                _ => (),
+            }
+        }
+        search
+    }
+    #[cfg(windows)]
+    {
+        std::env::var("USERDNSDOMAIN").ok().filter(|domain| !domain.is_empty())
+    }
+    #[cfg(not(any(unix, windows)))]
+    {
+        None
+    }
+}
+
+/// Return the PAC URL offered by the DHCP server, if it can be found.
+fn dhcp_url() -> Option<String> {
+    LEASE_DIRS.iter()
+        .filter_map(|dir| fs::read_dir(dir).ok())
+        .flatten()
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_name().to_string_lossy().ends_with(".lease")
+            || entry.file_name().to_string_lossy().ends_with(".leases"))
+        .filter_map(|entry| {
+            let modified = entry.metadata().and_then(|m| m.modified()).ok()?;
+            let url = parse_dhclient_leases(&fs::read_to_string(entry.path()).ok()?)?;
+            Some((modified, url))
+        })
+        .max()
+        .map(|(_, url)| url)
+}
+
+/// Web Proxy Auto-Discovery (requires the `wpad` feature.)
+///
+/// WPAD locates the PAC script of the network. The URL offered by the DHCP server (option 252)
+/// is tried first, if it can be found: only the lease files of `dhclient` are consulted, and
+/// only on Unix-like systems. Then, the script is looked for at `http://wpad.<domain>/wpad.dat`,
+/// where the domain is the DNS domain of the host, devolved one label at a time down to a domain
+/// with two labels; e.g., for `dept.corp.example.com`, the hosts `wpad.dept.corp.example.com`,
+/// `wpad.corp.example.com` and `wpad.example.com` are tried.
+///
+/// Anyone who controls a name on the devolution path, or the local network's DHCP server or
+/// DNS, can direct all of the application's traffic through their proxy. Discovery is never
+/// performed implicitly: call [`discover()`](#method.discover) when the application's users
+/// expect browser-like behavior, and consider fixing the domain with
+/// [`with_domain()`](#method.with_domain) instead of relying on the host's configuration. Note
+/// that devolution stops at two labels, which doesn't protect domains under a public suffix
+/// with two labels, such as `co.uk`.
+#[derive(Clone)]
+pub struct Wpad {
+    domain: Option<String>,
+    dhcp: bool,
+    dns: DnsHandle,
+}
+
+impl fmt::Debug for Wpad {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Wpad").field("domain", &self.domain).field("dhcp", &self.dhcp).finish()
+    }
+}
+
+impl Default for Wpad {
+    fn default() -> Wpad {
+        Wpad {
+            domain: None,
+            dhcp: true,
+            dns: DnsHandle(Arc::new(SystemDns)),
+        }
+    }
+}
+
+impl Wpad {
+    /// Create an instance which uses both DHCP and DNS with the host's domain.
+    pub fn new() -> Wpad {
+        Wpad::default()
+    }
+
+    /// Devolve the given domain instead of the host's DNS domain.
+    pub fn with_domain<S: Into<String>>(mut self, domain: S) -> Self {
+        self.domain = Some(domain.into());
+        self
+    }
+
+    /// Enable or disable looking for the URL offered by the DHCP server (default: enabled.)
+    pub fn with_dhcp(mut self, dhcp: bool) -> Self {
+        self.dhcp = dhcp;
+        self
+    }
+
+    /// Use the DNS resolver for finding the WPAD hosts, fetching the script, and for the name
+    /// lookups of the script.
+    pub fn with_dns_resolver<D: DnsResolve + 'static>(mut self, dns: D) -> Self {
+        self.dns = DnsHandle(Arc::new(dns));
+        self
+    }
+
+    /// Return the URLs to try, in order.
+    pub fn candidate_urls(&self) -> Vec<Url> {
+        let dhcp = if self.dhcp { dhcp_url() } else { None };
+        let domain = self.domain.clone().or_else(local_domain).unwrap_or_default();
+        dhcp.into_iter()
+            .chain(devolve(&domain).into_iter().map(|host| format!("http://{}/wpad.dat", host)))
+            .filter_map(|url| Url::parse(&url).map_err(|e| warn!("invalid WPAD URL '{}': {}", url, e)).ok())
+            .collect()
+    }
+
+    /// Find the PAC script, and return it with the URL it was loaded from.
+    ///
+    /// The first script which can be loaded is returned. If there's none, the result is `None`.
+    pub fn discover(&self) -> Option<(Url, PacScript)> {
+        for url in self.candidate_urls() {
+            let host = url.host_str().unwrap_or("");
+            if url.domain().is_some() && self.dns.0.resolve(host).map(|addrs| addrs.is_empty()).unwrap_or(true) {
+                debug!("WPAD host {} not found", host);
+                continue;
+            }
+            match PacScript::from_url_with(&url, self.dns.clone()) {
+                Ok(script) => return Some((url, script)),
+                Err(e) => debug!("could not load {}: {}", url, e),
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{devolve, parse_dhclient_leases};
+
+    #[test]
+    fn devolution() {
+        assert_eq!(devolve("Dept.Corp.example.com."), vec!["wpad.dept.corp.example.com", "wpad.corp.example.com", "wpad.example.com"]);
+        assert_eq!(devolve("example.com"), vec!["wpad.example.com"]);
+        assert!(devolve("localdomain").is_empty());
+        assert!(devolve("").is_empty());
+    }
+
+    #[test]
+    fn dhclient_leases() {
+        let leases = "lease {\n  interface \"eth0\";\n  option wpad \"http://old.example.com/wpad.dat\";\n}\n\
+            lease {\n  option routers 10.0.0.1;\n  option wpad \"http://pac.example.com/proxy.pac\\n\";\n}\n";
+        assert_eq!(parse_dhclient_leases(leases), Some("http://pac.example.com/proxy.pac".to_string()));
+        assert_eq!(parse_dhclient_leases("option option-252 \"http://a/\";"), Some("http://a/".to_string()));
+        assert_eq!(parse_dhclient_leases("option routers 10.0.0.1;"), None);
+    }
+}